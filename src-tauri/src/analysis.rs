@@ -0,0 +1,150 @@
+use crate::{
+    editor::Editor,
+    error::EditorError,
+    search::{parse_query, DistanceMetric, Motif, Pattern, PatternTarget, Peptide},
+    shared::AnalysisResult,
+    tasks::TaskContext,
+};
+
+/// An analysis which can run in the background, see [`crate::tasks::Tasks`].
+#[derive(serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Analysis {
+    Motif {
+        query: String,
+        circular: bool,
+    },
+    Approximate {
+        query: String,
+        circular: bool,
+        metric: DistanceMetric,
+        max_distance: usize,
+    },
+    Peptide {
+        query: String,
+    },
+    Pattern {
+        query: String,
+        target: PatternTarget,
+    },
+}
+
+pub type PreparedAnalysis = Box<dyn FnOnce(&TaskContext) -> AnalysisResult + Send>;
+
+impl Analysis {
+    /// Copies what the analysis needs from the editor, so that it can run
+    /// without holding on to the editor.
+    pub fn prepare(&self, editor: &Editor) -> Result<PreparedAnalysis, EditorError> {
+        let prepared: PreparedAnalysis = match self {
+            Analysis::Motif { query, circular } => {
+                let motif = parse_query(Motif::parse, query)?;
+                let (sequence, circular) = (editor.sequence.search_index(), *circular);
+                Box::new(move |context| {
+                    let hits =
+                        motif.find_all_with_progress(&sequence, circular, &mut |done, total| {
+                            context.report(done, total)
+                        });
+                    AnalysisResult::Hits(hits.unwrap_or_default())
+                })
+            }
+            Analysis::Approximate {
+                query,
+                circular,
+                metric,
+                max_distance,
+            } => {
+                let motif = parse_query(
+                    |query| Motif::parse_approximate(query, *max_distance),
+                    query,
+                )?;
+                let sequence = editor.sequence.search_index();
+                let (circular, metric, max_distance) = (*circular, *metric, *max_distance);
+                Box::new(move |context| {
+                    let hits = motif.find_approximate_with_progress(
+                        &sequence,
+                        circular,
+                        metric,
+                        max_distance,
+                        &mut |done, total| context.report(done, total),
+                    );
+                    // Aborted searches are only reported as cancelled, so the hits don't matter
+                    AnalysisResult::ApproximateHits(hits.unwrap_or_default())
+                })
+            }
+            Analysis::Peptide { query } => {
+                let peptide = parse_query(Peptide::parse, query)?;
+                let sequence = editor.sequence.search_index();
+                Box::new(move |context| {
+                    let hits = peptide.find_all_with_progress(&sequence, &mut |done, total| {
+                        context.report(done, total)
+                    });
+                    AnalysisResult::Hits(hits.unwrap_or_default())
+                })
+            }
+            Analysis::Pattern { query, target } => {
+                let pattern = parse_query(Pattern::parse, query)?;
+                let (sequence, target) = (editor.sequence.search_index(), *target);
+                Box::new(move |context| {
+                    let hits =
+                        pattern.find_all_with_progress(&sequence, target, &mut |done, total| {
+                            context.report(done, total)
+                        });
+                    AnalysisResult::Hits(hits.unwrap_or_default())
+                })
+            }
+        };
+        Ok(prepared)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::Analysis;
+    use crate::{
+        editor::Editor,
+        error::EditorError,
+        shared::{AnalysisResult, SearchHit, Strand},
+        tasks::{TaskOutcome, Tasks},
+    };
+
+    #[test]
+    fn test_run_in_background() {
+        let mut editor = Editor::default();
+        editor.insert_all("AAGAATTCAA".to_string()).unwrap();
+        let analysis = Analysis::Motif {
+            query: "GAATTC".to_string(),
+            circular: false,
+        };
+        let prepared = analysis.prepare(&editor).unwrap();
+        // The analysis works on a copy of the sequence
+        editor.insert_all("GG".to_string()).unwrap();
+
+        let tasks = Tasks::default();
+        let (sender, receiver) = mpsc::channel();
+        tasks.spawn(
+            "vector".to_string(),
+            |_, _, _| {},
+            prepared,
+            move |_, result| sender.send(result).unwrap(),
+        );
+        assert_eq!(
+            receiver.recv().unwrap(),
+            TaskOutcome::Done(AnalysisResult::Hits(vec![SearchHit {
+                start: 2,
+                end: 8,
+                strand: Strand::Forward,
+            }]))
+        );
+
+        let invalid = Analysis::Peptide {
+            query: "?".to_string(),
+        };
+        assert_eq!(
+            invalid.prepare(&editor).err(),
+            Some(EditorError::InvalidQuery {
+                query: "?".to_string()
+            })
+        );
+    }
+}
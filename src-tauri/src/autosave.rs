@@ -0,0 +1,163 @@
+use std::{collections::HashMap, fs, path::PathBuf, time::Duration};
+
+use parking_lot::{Mutex, RwLock};
+
+use crate::{
+    documents::{DocumentId, Documents},
+    project::{project_file_name, read_projects, ProjectFile, ProjectInfo},
+};
+
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+struct AutosaveState {
+    info: ProjectInfo,
+    /// What was written on the last autosave, to skip unchanged projects.
+    last_saved: Option<String>,
+}
+
+/// Periodically writes open projects to the app data directory.
+///
+/// The files are removed when the app exits normally, so any file found on
+/// launch belongs to a session which crashed and can be recovered.
+pub struct Autosave {
+    dir: PathBuf,
+    projects: RwLock<HashMap<DocumentId, AutosaveState>>,
+    recovered: RwLock<Vec<ProjectFile>>,
+    /// Held while files are written, so that a removed project's file isn't
+    /// written again by a save which was already running.
+    saving: Mutex<()>,
+}
+
+impl Autosave {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            recovered: RwLock::new(read_projects(&dir)),
+            dir,
+            projects: RwLock::default(),
+            saving: Mutex::default(),
+        }
+    }
+
+    pub fn info(&self, id: &str) -> ProjectInfo {
+        self.projects
+            .read()
+            .get(id)
+            .map(|state| state.info.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set_info(&self, id: DocumentId, info: ProjectInfo) {
+        self.projects.write().entry(id).or_default().info = info;
+    }
+
+    /// Writes every open document which changed since the last autosave,
+    /// also those without project info.
+    ///
+    /// The projects are only locked briefly, so that commands don't wait for
+    /// the editors being read.
+    pub fn save_all(&self, documents: &Documents) {
+        let _saving = self.saving.lock();
+        if fs::create_dir_all(&self.dir).is_err() {
+            return;
+        }
+        for id in documents.list() {
+            // Closed in the meantime
+            let editor = match documents.get(&id) {
+                Ok(editor) => editor,
+                Err(_) => continue,
+            };
+            let project = ProjectFile::new(id.clone(), self.info(&id), &editor.read());
+            let json = match project.to_json() {
+                Ok(json) => json,
+                Err(_) => continue,
+            };
+            let last_saved = self
+                .projects
+                .read()
+                .get(&id)
+                .and_then(|state| state.last_saved.clone());
+            if last_saved.as_ref() == Some(&json) {
+                continue;
+            }
+            // Failed writes are retried on the next autosave
+            if project.write(&self.path(&id)).is_ok() {
+                self.projects.write().entry(id).or_default().last_saved = Some(json);
+            }
+        }
+    }
+
+    /// Stops autosaving a project and removes its file.
+    ///
+    /// The document has to be closed first, or the next autosave writes it again.
+    pub fn remove(&self, id: &str) {
+        let _saving = self.saving.lock();
+        self.projects.write().remove(id);
+        let _ = fs::remove_file(self.path(id));
+    }
+
+    /// Removes all autosaved files, on a normal exit.
+    pub fn clear(&self) {
+        let ids = Vec::from_iter(self.projects.read().keys().cloned());
+        for id in ids {
+            self.remove(&id);
+        }
+    }
+
+    pub fn recovered(&self) -> Vec<(DocumentId, ProjectInfo)> {
+        self.recovered
+            .read()
+            .iter()
+            .map(|project| (project.id.clone(), project.info.clone()))
+            .collect()
+    }
+
+    /// Removes a recovered project from the list, returning it.
+    pub fn take_recovered(&self, id: &str) -> Option<ProjectFile> {
+        let mut recovered = self.recovered.write();
+        let index = recovered.iter().position(|project| project.id == id)?;
+        Some(recovered.remove(index))
+    }
+
+    /// Deletes a recovered project. Returns `false` if there is none with that id.
+    pub fn discard_recovered(&self, id: &str) -> bool {
+        if self.take_recovered(id).is_none() {
+            return false;
+        }
+        if !self.projects.read().contains_key(id) {
+            let _ = fs::remove_file(self.path(id));
+        }
+        true
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(project_file_name(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::Autosave;
+    use crate::{documents::Documents, project::ProjectFile};
+
+    #[test]
+    fn test_save_all_saves_every_document() {
+        let dir = env::temp_dir().join("test_autosave_save_all");
+        let autosave = Autosave::new(dir.clone());
+        let documents = Documents::default();
+        let (editor, _) = documents.open("without-info");
+        editor.write().insert_all("ACgt".to_string()).unwrap();
+
+        autosave.save_all(&documents);
+        let path = autosave.path("without-info");
+        assert_eq!(ProjectFile::read(&path).unwrap().sequence, "ACgt");
+
+        documents.close("without-info");
+        autosave.remove("without-info");
+        autosave.save_all(&documents);
+        assert!(!path.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
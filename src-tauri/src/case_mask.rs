@@ -0,0 +1,127 @@
+use std::ops::Range;
+
+use plasmid::{traits::ToLetter, uni::IupacNucleotide};
+
+/// Positions of lowercase (soft-masked) nucleotides.
+///
+/// Nucleotides themselves don't have a case, so the case of the letters they
+/// were read from is kept as sorted, non-overlapping ranges next to them.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct CaseMask {
+    ranges: Vec<Range<usize>>,
+}
+
+impl CaseMask {
+    /// Builds a mask from the case of every position, in order.
+    pub fn from_lowercase(lowercase: impl IntoIterator<Item = bool>) -> Self {
+        let mut mask = Self::default();
+        for (position, is_lowercase) in lowercase.into_iter().enumerate() {
+            if is_lowercase {
+                mask.push(position..position + 1);
+            }
+        }
+        mask
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn is_lowercase(&self, position: usize) -> bool {
+        let index = self.ranges.partition_point(|range| range.end <= position);
+        self.ranges
+            .get(index)
+            .map_or(false, |range| range.start <= position)
+    }
+
+    /// The part of the mask within `range`, relative to its start.
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        let mut slice = Self::default();
+        for lowercase in &self.ranges {
+            let start = lowercase.start.max(range.start);
+            let end = lowercase.end.min(range.end);
+            if start < end {
+                slice.push(start - range.start..end - range.start);
+            }
+        }
+        slice
+    }
+
+    /// Adds the ranges of another mask, shifted by `offset`. Everything in
+    /// this mask has to lie before `offset`.
+    pub fn append(&mut self, offset: usize, other: &CaseMask) {
+        for range in &other.ranges {
+            self.push(range.start + offset..range.end + offset);
+        }
+    }
+
+    /// Makes room for `len` positions at `position`, whose case is given by `inserted`.
+    pub fn insert(&mut self, position: usize, len: usize, inserted: &CaseMask) {
+        let mut mask = self.slice(0..position);
+        mask.append(position, &inserted.slice(0..len));
+        mask.append(position + len, &self.slice(position..usize::MAX));
+        *self = mask;
+    }
+
+    /// Removes a range of positions, moving the positions behind it back.
+    pub fn remove(&mut self, range: Range<usize>) {
+        let mut mask = self.slice(0..range.start);
+        mask.append(range.start, &self.slice(range.end..usize::MAX));
+        *self = mask;
+    }
+
+    /// Memory used on the heap.
+    pub fn heap_size(&self) -> usize {
+        self.ranges.capacity() * std::mem::size_of::<Range<usize>>()
+    }
+
+    /// Adds a range behind all others, merging it with the last one if they touch.
+    fn push(&mut self, range: Range<usize>) {
+        match self.ranges.last_mut() {
+            Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+            _ => self.ranges.push(range),
+        }
+    }
+}
+
+pub fn cased_letter(nucleotide: &IupacNucleotide, lowercase: bool) -> char {
+    let letter = nucleotide.to_letter();
+    if lowercase {
+        letter.to_ascii_lowercase()
+    } else {
+        letter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CaseMask;
+
+    fn mask(case: &str) -> CaseMask {
+        CaseMask::from_lowercase(case.chars().map(|c| c.is_ascii_lowercase()))
+    }
+
+    #[test]
+    fn test_from_lowercase() {
+        let lowercase = mask("AaaCGtT");
+        assert_eq!(lowercase.ranges, [1..3, 5..6]);
+        assert!(lowercase.is_lowercase(2));
+        assert!(!lowercase.is_lowercase(3));
+        assert!(lowercase.is_lowercase(5));
+        assert!(!lowercase.is_lowercase(6));
+    }
+
+    #[test]
+    fn test_edits() {
+        let mut lowercase = mask("AaaCGtT");
+        lowercase.insert(2, 3, &mask("GcT"));
+        assert_eq!(lowercase, mask("AaGcTaCGtT"));
+        assert_eq!(lowercase.slice(3..6), mask("cTa"));
+
+        lowercase.remove(1..4);
+        assert_eq!(lowercase, mask("ATaCGtT"));
+        lowercase.insert(3, 1, &mask("g"));
+        assert_eq!(lowercase.ranges, [2..4, 6..7]);
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    collections::BTreeSet,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    editor::Selection,
+    sequence::Sequence,
+    shared::{CheckpointData, DiffHunk},
+};
+
+/// Above this number of changed nucleotides, diffs are reported as a single hunk.
+const MAX_DIFF_EDITS: usize = 1000;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct EditorSnapshot {
+    pub cursor_pos: usize,
+    pub selection: Option<Selection>,
+    #[serde(with = "cased_letters")]
+    pub sequence: Sequence,
+}
+
+impl EditorSnapshot {
+    fn is_well_formed(&self) -> bool {
+        let len = self.sequence.len();
+        self.cursor_pos <= len
+            && self.selection.as_ref().map_or(true, |selection| {
+                selection.start <= selection.end && selection.end <= len
+            })
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Checkpoint {
+    pub id: usize,
+    pub name: String,
+    #[serde(with = "millis")]
+    pub created_at: SystemTime,
+    pub snapshot: EditorSnapshot,
+}
+
+impl From<&Checkpoint> for CheckpointData {
+    fn from(checkpoint: &Checkpoint) -> Self {
+        CheckpointData {
+            id: checkpoint.id,
+            name: checkpoint.name.clone(),
+            created_at: checkpoint
+                .created_at
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_millis() as u64),
+            bp_count: checkpoint.snapshot.sequence.len(),
+        }
+    }
+}
+
+/// Named versions of a document, kept independently of the undo history.
+///
+/// They are stored in project files together with the history.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default, Clone)]
+pub struct Checkpoints {
+    checkpoints: Vec<Checkpoint>,
+    next_id: usize,
+}
+
+impl Checkpoints {
+    pub fn clear(&mut self) {
+        self.checkpoints.clear();
+    }
+
+    pub fn create(&mut self, name: String, snapshot: EditorSnapshot) -> &Checkpoint {
+        self.checkpoints.push(Checkpoint {
+            id: self.next_id,
+            name,
+            created_at: SystemTime::now(),
+            snapshot,
+        });
+        self.next_id += 1;
+        self.checkpoints.last().unwrap()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Checkpoint> {
+        self.checkpoints
+            .iter()
+            .find(|checkpoint| checkpoint.id == id)
+    }
+
+    /// Returns `false` if the checkpoint doesn't exist.
+    pub fn remove(&mut self, id: usize) -> bool {
+        let len = self.checkpoints.len();
+        self.checkpoints.retain(|checkpoint| checkpoint.id != id);
+        self.checkpoints.len() != len
+    }
+
+    pub fn list(&self) -> Vec<CheckpointData> {
+        self.checkpoints.iter().map(CheckpointData::from).collect()
+    }
+
+    /// Checks that saved checkpoints have unique ids below `next_id` and
+    /// that their cursors and selections lie within their sequences.
+    pub fn is_well_formed(&self) -> bool {
+        let mut ids = BTreeSet::new();
+        self.checkpoints.iter().all(|checkpoint| {
+            checkpoint.id < self.next_id
+                && ids.insert(checkpoint.id)
+                && checkpoint.snapshot.is_well_formed()
+        })
+    }
+}
+
+/// Stores a sequence as a string of IUPAC letters, keeping their case.
+mod cased_letters {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use crate::{editor::parse_nucleotides, sequence::Sequence};
+
+    pub fn serialize<S: Serializer>(sequence: &Sequence, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&sequence.letters(0..sequence.len()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Sequence, D::Error> {
+        let (nucleotides, lowercase) = parse_nucleotides(&String::deserialize(deserializer)?)
+            .map_err(|_| D::Error::custom("invalid nucleotide letters"))?;
+        Ok(Sequence::from_nucleotides(&nucleotides, &lowercase))
+    }
+}
+
+/// Stores a point in time as milliseconds since the unix epoch.
+mod millis {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(
+            time.duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_millis() as u64),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        Ok(UNIX_EPOCH + Duration::from_millis(u64::deserialize(deserializer)?))
+    }
+}
+
+/// Computes the regions in which `new` differs from `old`.
+#[cfg(test)]
+pub fn diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<DiffHunk> {
+    diff_by(old.len(), new.len(), |x, y| old[x] == new[y])
+}
+
+/// Computes the regions in which `new` differs from `old`, using Myers' diff
+/// algorithm. Nucleotides are compared in place, including their case, so
+/// that neither sequence has to be unpacked.
+pub fn diff_sequences(old: &Sequence, new: &Sequence) -> Vec<DiffHunk> {
+    let (old_index, new_index) = (old.search_index(), new.search_index());
+    diff_by(old.len(), new.len(), |x, y| {
+        old_index.mask(x) == new_index.mask(y) && old.is_lowercase(x) == new.is_lowercase(y)
+    })
+}
+
+/// Diffs two sequences of the given lengths, where `same(x, y)` tells whether
+/// position `x` of the old sequence equals position `y` of the new one.
+fn diff_by(old_len: usize, new_len: usize, same: impl Fn(usize, usize) -> bool) -> Vec<DiffHunk> {
+    let prefix = (0..old_len.min(new_len))
+        .take_while(|&index| same(index, index))
+        .count();
+    let suffix = (1..=(old_len - prefix).min(new_len - prefix))
+        .take_while(|&back| same(old_len - back, new_len - back))
+        .count();
+    let (n, m) = (old_len - prefix - suffix, new_len - prefix - suffix);
+    let same = |x: usize, y: usize| same(prefix + x, prefix + y);
+    let hunk = |old_start, old_end, new_start, new_end| DiffHunk {
+        old_start: prefix + old_start,
+        old_end: prefix + old_end,
+        new_start: prefix + new_start,
+        new_end: prefix + new_end,
+    };
+
+    if n == 0 && m == 0 {
+        return Vec::new();
+    }
+    if n == 0 || m == 0 {
+        return vec![hunk(0, n, 0, m)];
+    }
+    let trace = match shortest_edit_trace(n, m, &same) {
+        Some(trace) => trace,
+        None => return vec![hunk(0, n, 0, m)],
+    };
+
+    // Walk the trace backwards, collecting the matching diagonals
+    let mut matches = Vec::new();
+    let (mut x, mut y) = (n as isize, m as isize);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let at = |k: isize| v[(k + d) as usize];
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = if d == 0 { 0 } else { at(prev_k) };
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            matches.push((x as usize, y as usize));
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    matches.reverse();
+    matches.push((n, m));
+
+    let mut hunks = Vec::new();
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for (old_match, new_match) in matches {
+        if old_match > old_pos || new_match > new_pos {
+            hunks.push(hunk(old_pos, old_match, new_pos, new_match));
+        }
+        old_pos = old_match + 1;
+        new_pos = new_match + 1;
+    }
+    hunks
+}
+
+/// Runs the forward pass of Myers' algorithm. `trace[d]` holds the furthest
+/// reaching x for every diagonal `k` in `-d..=d` before step `d`.
+///
+/// Returns `None` if more than `MAX_DIFF_EDITS` edits are needed.
+fn shortest_edit_trace(
+    n: usize,
+    m: usize,
+    same: &dyn Fn(usize, usize) -> bool,
+) -> Option<Vec<Vec<isize>>> {
+    let (n, m) = (n as isize, m as isize);
+    let max = (n + m).min(MAX_DIFF_EDITS as isize);
+    let offset = max + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    let mut trace = Vec::new();
+    for d in 0..=max {
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let index = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+                v[index + 1]
+            } else {
+                v[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && same(x as usize, y as usize) {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+            if x >= n && y >= m {
+                return Some(trace);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff;
+    use crate::shared::DiffHunk;
+
+    fn hunk(old_start: usize, old_end: usize, new_start: usize, new_end: usize) -> DiffHunk {
+        DiffHunk {
+            old_start,
+            old_end,
+            new_start,
+            new_end,
+        }
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        assert_eq!(diff(b"ACGT", b"ACGT"), vec![]);
+        assert_eq!(diff(b"", b""), vec![]);
+    }
+
+    #[test]
+    fn test_diff_point_mutations() {
+        assert_eq!(
+            diff(b"AAACAAAGAAA", b"AAATAAAGGAA"),
+            vec![hunk(3, 4, 3, 4), hunk(8, 9, 8, 9)]
+        );
+    }
+
+    #[test]
+    fn test_diff_insertion_and_deletion() {
+        assert_eq!(
+            diff(b"ACGTACGT", b"ACGGGTACT"),
+            vec![hunk(3, 3, 3, 5), hunk(6, 7, 8, 8)]
+        );
+        assert_eq!(diff(b"", b"ACG"), vec![hunk(0, 0, 0, 3)]);
+        assert_eq!(diff(b"ACG", b"A"), vec![hunk(1, 3, 1, 1)]);
+    }
+}
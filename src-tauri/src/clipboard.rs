@@ -0,0 +1,297 @@
+use std::{fs, path::PathBuf};
+
+use parking_lot::RwLock;
+
+use crate::{
+    documents::DocumentId,
+    editor::Editor,
+    error::EditorError,
+    history::NodeId,
+    project::{Feature, Primer, ProjectInfo},
+    shared::{ClipboardPayload, Strand},
+};
+
+struct ClipboardEntry {
+    payload: ClipboardPayload,
+    /// The history node of the cut, until the fragment was pasted.
+    cut_node: Option<NodeId>,
+}
+
+/// Structured payload of the last fragment copied in the app.
+///
+/// The system clipboard only holds the plain text, so the payload is kept
+/// here and used as long as the system clipboard still contains that text.
+/// It is also written to a file, so that pasting keeps its origin and case
+/// after a restart. Other apps only ever see the plain text.
+#[derive(Default)]
+pub struct Clipboard {
+    entry: RwLock<Option<ClipboardEntry>>,
+    path: Option<PathBuf>,
+}
+
+impl Clipboard {
+    /// Restores the payload written by a previous session, if there is one.
+    pub fn open(path: PathBuf) -> Self {
+        let payload = fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok());
+        Self {
+            // The cut was already committed, so a restored payload only pastes
+            entry: RwLock::new(payload.map(|payload| ClipboardEntry {
+                payload,
+                cut_node: None,
+            })),
+            path: Some(path),
+        }
+    }
+
+    /// Builds the payload for the selection of an editor, taking along the
+    /// features and primers of the document which overlap it.
+    ///
+    /// Fails with [`EditorError::EmptySelection`] if nothing is selected.
+    pub fn payload(
+        editor: &Editor,
+        info: &ProjectInfo,
+        document: DocumentId,
+        strand: Strand,
+        is_cut: bool,
+    ) -> Result<ClipboardPayload, EditorError> {
+        let (selection, text) = editor
+            .get_selected_strand(strand)
+            .ok_or(EditorError::EmptySelection)?;
+        let fragment = Fragment {
+            start: selection.start,
+            end: selection.end,
+            strand,
+        };
+        Ok(ClipboardPayload {
+            text,
+            source_document: document,
+            start: selection.start,
+            end: selection.end,
+            strand,
+            is_cut,
+            features: info
+                .features
+                .iter()
+                .filter_map(|feature| fragment.feature(feature))
+                .collect(),
+            primers: info
+                .primers
+                .iter()
+                .filter_map(|primer| fragment.primer(primer))
+                .collect(),
+        })
+    }
+
+    /// Moves the features and primers of a payload from fragment positions
+    /// to the position in the target document the fragment was pasted at.
+    pub fn rebase(payload: &mut ClipboardPayload, position: usize) {
+        for feature in &mut payload.features {
+            feature.start += position;
+            feature.end += position;
+        }
+        for primer in &mut payload.primers {
+            if let Some(start) = &mut primer.start {
+                *start += position;
+            }
+        }
+    }
+
+    pub fn set(&self, payload: ClipboardPayload, cut_node: Option<NodeId>) {
+        if let Some(path) = &self.path {
+            // Only a paste after a restart depends on the file, so failing is fine
+            if let (Some(dir), Ok(json)) = (path.parent(), serde_json::to_string(&payload)) {
+                let _ = fs::create_dir_all(dir).and_then(|_| fs::write(path, json));
+            }
+        }
+        *self.entry.write() = Some(ClipboardEntry { payload, cut_node });
+    }
+
+    /// Returns the payload if it belongs to the text on the system clipboard,
+    /// and the node of its cut if that wasn't pasted yet.
+    pub fn take_matching(&self, text: &str) -> Option<(ClipboardPayload, Option<NodeId>)> {
+        let mut entry = self.entry.write();
+        let entry = entry
+            .as_mut()
+            .filter(|entry| entry.payload.text == text.trim())?;
+        Some((entry.payload.clone(), entry.cut_node.take()))
+    }
+}
+
+/// A copied range, as read on `strand`.
+struct Fragment {
+    start: usize,
+    end: usize,
+    strand: Strand,
+}
+
+impl Fragment {
+    /// Converts a forward strand range within the fragment into a range of `text`.
+    fn relative(&self, start: usize, end: usize) -> (usize, usize) {
+        match self.strand {
+            Strand::Forward => (start - self.start, end - self.start),
+            Strand::Reverse => (self.end - end, self.end - start),
+        }
+    }
+
+    fn relative_strand(&self, strand: Strand) -> Strand {
+        match (self.strand, strand) {
+            (Strand::Forward, strand) => strand,
+            (Strand::Reverse, Strand::Forward) => Strand::Reverse,
+            (Strand::Reverse, Strand::Reverse) => Strand::Forward,
+        }
+    }
+
+    /// The part of a feature within the fragment, if they overlap.
+    fn feature(&self, feature: &Feature) -> Option<Feature> {
+        let start = feature.start.max(self.start);
+        let end = feature.end.min(self.end);
+        if start >= end {
+            return None;
+        }
+        let (start, end) = self.relative(start, end);
+        Some(Feature {
+            start,
+            end,
+            strand: self.relative_strand(feature.strand),
+            ..feature.clone()
+        })
+    }
+
+    /// The primer if it binds within the fragment. Primers which bind partly
+    /// outside of it are kept, but lose their position.
+    fn primer(&self, primer: &Primer) -> Option<Primer> {
+        let start = primer.start?;
+        let end = start + primer.sequence.len();
+        if start >= self.end || end <= self.start {
+            return None;
+        }
+        let start = (start >= self.start && end <= self.end).then(|| self.relative(start, end).0);
+        Some(Primer {
+            start,
+            strand: self.relative_strand(primer.strand),
+            ..primer.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::Clipboard;
+    use crate::{
+        editor::{Editor, SelectionMovement},
+        project::{Feature, Primer, ProjectInfo},
+        shared::{ClipboardPayload, Strand},
+    };
+
+    fn feature(name: &str, start: usize, end: usize, strand: Strand) -> Feature {
+        Feature {
+            name: name.to_string(),
+            kind: "misc_feature".to_string(),
+            start,
+            end,
+            strand,
+        }
+    }
+
+    fn primer(name: &str, sequence: &str, start: usize) -> Primer {
+        Primer {
+            name: name.to_string(),
+            sequence: sequence.to_string(),
+            start: Some(start),
+            strand: Strand::Forward,
+        }
+    }
+
+    #[test]
+    fn test_payload_survives_restart() {
+        let path = env::temp_dir().join("test_clipboard_restart.json");
+        let payload = ClipboardPayload {
+            text: "ACgt".to_string(),
+            source_document: "project".to_string(),
+            start: 2,
+            end: 6,
+            strand: Strand::Forward,
+            is_cut: true,
+            features: vec![feature("lac", 0, 2, Strand::Forward)],
+            primers: Vec::new(),
+        };
+        Clipboard::open(path.clone()).set(payload.clone(), Some(3));
+
+        let restored = Clipboard::open(path.clone()).take_matching("ACgt\n");
+        fs::remove_file(&path).unwrap();
+        assert_eq!(restored, Some((payload, None)));
+    }
+
+    #[test]
+    fn test_payload_takes_overlapping_metadata() {
+        let mut editor = Editor::default();
+        editor.insert_all("AAAACCCCGGGGTTTT".to_string()).unwrap();
+        editor
+            .move_selection(SelectionMovement::Set { start: 4, end: 12 })
+            .unwrap();
+        let info = ProjectInfo {
+            features: vec![
+                feature("before", 0, 4, Strand::Forward),
+                feature("across", 2, 6, Strand::Forward),
+                feature("inside", 8, 10, Strand::Reverse),
+            ],
+            primers: vec![
+                primer("inside", "CCGG", 6),
+                primer("across", "GGTT", 10),
+                primer("after", "TT", 14),
+            ],
+            ..ProjectInfo::default()
+        };
+
+        let payload = Clipboard::payload(
+            &editor,
+            &info,
+            "project".to_string(),
+            Strand::Forward,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            payload.features,
+            vec![
+                feature("across", 0, 2, Strand::Forward),
+                feature("inside", 4, 6, Strand::Reverse),
+            ]
+        );
+        assert_eq!(payload.primers[0], primer("inside", "CCGG", 2));
+        assert_eq!(payload.primers[1].start, None);
+        assert_eq!(payload.primers.len(), 2);
+
+        // Read on the reverse strand, positions are mirrored and strands swapped
+        let payload = Clipboard::payload(
+            &editor,
+            &info,
+            "project".to_string(),
+            Strand::Reverse,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            payload.features,
+            vec![
+                feature("across", 6, 8, Strand::Reverse),
+                feature("inside", 2, 4, Strand::Forward),
+            ]
+        );
+        assert_eq!(payload.primers[0].start, Some(2));
+        assert_eq!(payload.primers[0].strand, Strand::Reverse);
+
+        let mut payload = payload;
+        Clipboard::rebase(&mut payload, 100);
+        assert_eq!(
+            payload.features[1],
+            feature("inside", 102, 104, Strand::Forward)
+        );
+        assert_eq!(payload.primers[0].start, Some(102));
+        assert_eq!(payload.primers[1].start, None);
+    }
+}
@@ -0,0 +1,76 @@
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::RwLock;
+
+use crate::{editor::Editor, error::EditorError};
+
+/// The id of the project a document belongs to.
+pub type DocumentId = String;
+
+/// All documents which are open in the editor, each with its own history and selection.
+#[derive(Default)]
+pub struct Documents {
+    editors: RwLock<HashMap<DocumentId, Arc<RwLock<Editor>>>>,
+}
+
+impl Documents {
+    /// Returns the editor of an open document.
+    pub fn get(&self, id: &str) -> Result<Arc<RwLock<Editor>>, EditorError> {
+        self.editors
+            .read()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| EditorError::UnknownDocument { id: id.to_string() })
+    }
+
+    /// Returns the editor of a document, and whether it had to be opened.
+    pub fn open(&self, id: &str) -> (Arc<RwLock<Editor>>, bool) {
+        let mut editors = self.editors.write();
+        if let Some(editor) = editors.get(id) {
+            return (editor.clone(), false);
+        }
+        let editor = Arc::<RwLock<Editor>>::default();
+        editors.insert(id.to_string(), editor.clone());
+        (editor, true)
+    }
+
+    /// Returns `false` if the document wasn't open.
+    pub fn close(&self, id: &str) -> bool {
+        self.editors.write().remove(id).is_some()
+    }
+
+    pub fn list(&self) -> Vec<DocumentId> {
+        let mut ids = Vec::from_iter(self.editors.read().keys().cloned());
+        ids.sort();
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Documents;
+    use crate::error::EditorError;
+
+    #[test]
+    fn test_documents_are_independent() {
+        let documents = Documents::default();
+        let (vector, is_new) = documents.open("vector");
+        assert!(is_new);
+        vector.write().insert_all("ACGT".to_string()).unwrap();
+        let (insert, _) = documents.open("insert");
+        insert.write().insert_all("GG".to_string()).unwrap();
+
+        let (vector, is_new) = documents.open("vector");
+        assert!(!is_new);
+        assert_eq!(vector.read().sequence.len(), 4);
+        assert_eq!(documents.get("insert").unwrap().read().sequence.len(), 2);
+        assert_eq!(documents.list(), ["insert", "vector"]);
+
+        assert!(documents.close("vector"));
+        assert!(matches!(
+            documents.get("vector"),
+            Err(EditorError::UnknownDocument { id }) if id == "vector"
+        ));
+        assert!(!documents.close("vector"));
+    }
+}
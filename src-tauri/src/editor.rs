@@ -1,10 +1,20 @@
-use std::collections::VecDeque;
-
-use plasmid::{traits::TryFromLetter, uni::IupacNucleotide};
+use plasmid::{
+    traits::{Nucleotide, TryFromLetter},
+    uni::IupacNucleotide,
+};
 
 use crate::{
-    history::{EditorSnapshot, EditorSnapshotHistory},
-    shared::DisplayCodon,
+    case_mask::{cased_letter, CaseMask},
+    checkpoint::{diff_sequences, Checkpoints, EditorSnapshot},
+    error::EditorError,
+    history::{EditOperation, EditorHistory, HistoryStep, NodeId, SavedHistory},
+    packed::PackedNucleotides,
+    search::{DistanceMetric, Motif, Pattern, PatternTarget, Peptide},
+    sequence::Sequence,
+    shared::{
+        ApproximateHit, ChangeKind, CheckpointData, DiffHunk, DisplayCodon, PackedSequenceItems,
+        SearchHit, SequenceChange, SequenceItem, SequenceItems, Strand,
+    },
 };
 
 pub enum CursorMovement {
@@ -23,7 +33,7 @@ pub enum SelectionMovement {
     All,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Selection {
     pub start: usize,
     pub end: usize,
@@ -37,28 +47,70 @@ impl Selection {
     }
 }
 
+/// The part of the sequence whose display codons are outdated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DirtyRegion {
+    /// Start of the changed nucleotides, in positions of the current sequence.
+    start: usize,
+    /// End of the changed nucleotides, in positions of the current sequence.
+    end: usize,
+    /// Change in length since the codons were last computed.
+    delta: isize,
+}
+
+impl DirtyRegion {
+    /// Grows a region by an operation and maps it to the positions after the operation.
+    fn extend(region: Option<Self>, operation: &EditOperation) -> Self {
+        let (position, len) = match operation {
+            EditOperation::Insert {
+                position,
+                nucleotides,
+                ..
+            }
+            | EditOperation::Delete {
+                position,
+                nucleotides,
+                ..
+            } => (*position, nucleotides.len()),
+        };
+        let Self { start, end, delta } = region.unwrap_or(Self {
+            start: position,
+            end: position,
+            delta: 0,
+        });
+
+        match operation {
+            EditOperation::Insert { .. } => Self {
+                start: start.min(position),
+                end: if end >= position { end + len } else { end }.max(position + len),
+                delta: delta + len as isize,
+            },
+            EditOperation::Delete { .. } => Self {
+                start: start.min(position),
+                end: if end >= position + len {
+                    end - len
+                } else {
+                    end.min(position)
+                }
+                .max(position),
+                delta: delta - len as isize,
+            },
+        }
+    }
+}
+
+#[derive(Default)]
 pub struct Editor {
     pub sequence_dirty: bool,
     pub cursor_pos: usize,
-    pub sequence: VecDeque<IupacNucleotide>,
+    pub sequence: Sequence,
     pub codons: Vec<DisplayCodon>,
     pub selection: Option<Selection>,
-    pub history: EditorSnapshotHistory,
-}
-
-impl Default for Editor {
-    fn default() -> Self {
-        let mut editor = Self {
-            sequence_dirty: false,
-            cursor_pos: 0,
-            sequence: VecDeque::new(),
-            codons: Vec::new(),
-            selection: None,
-            history: EditorSnapshotHistory::default(),
-        };
-        editor.history.push(editor.snapshot());
-        editor
-    }
+    pub history: EditorHistory,
+    pub checkpoints: Checkpoints,
+    dirty_region: Option<DirtyRegion>,
+    /// Changes since the last call to `take_changes`.
+    changes: Vec<SequenceChange>,
 }
 
 // #region Private API
@@ -68,36 +120,148 @@ impl Editor {
         EditorSnapshot {
             cursor_pos: self.cursor_pos,
             selection: self.selection.clone(),
-            sequence: Vec::from_iter(self.sequence.iter().cloned()),
+            sequence: self.sequence.clone(),
         }
     }
 
-    fn apply_snapshot(&mut self, snapshot: EditorSnapshot) {
-        self.cursor_pos = snapshot.cursor_pos;
-        self.selection = snapshot.selection.clone();
-        let new_sequence = VecDeque::from_iter(snapshot.sequence.iter().cloned());
-        let became_dirty = self.sequence != new_sequence;
-        self.sequence = new_sequence;
-        self.sequence_dirty = became_dirty;
+    fn check_index(&self, index: usize) -> Result<(), EditorError> {
+        if index > self.sequence.len() {
+            return Err(EditorError::IndexOutOfRange {
+                index,
+                len: self.sequence.len(),
+            });
+        }
+        Ok(())
     }
 
-    #[inline]
-    fn inner_insert_nucleotide(&mut self, nucleotide: IupacNucleotide) {
-        match self.cursor_pos {
-            0 => self.sequence.push_front(nucleotide),
-            i if i == self.sequence.len() => self.sequence.push_back(nucleotide),
-            i => self.sequence.insert(i, nucleotide),
+    fn begin_edit(&mut self) {
+        self.history.begin(self.cursor_pos, self.selection.clone());
+    }
+
+    fn commit_edit(&mut self) {
+        self.history.commit(self.cursor_pos, self.selection.clone());
+    }
+
+    /// Commits an edit which may be grouped with directly preceding typing.
+    fn commit_typing_edit(&mut self) {
+        self.history
+            .commit_coalescing(self.cursor_pos, self.selection.clone());
+    }
+
+    fn mark_dirty(&mut self, operation: &EditOperation) {
+        self.changes.push(match operation {
+            EditOperation::Insert {
+                position,
+                nucleotides,
+                ..
+            } => SequenceChange {
+                kind: ChangeKind::Inserted,
+                start: *position,
+                end: position + nucleotides.len(),
+            },
+            EditOperation::Delete {
+                position,
+                nucleotides,
+                ..
+            } => SequenceChange {
+                kind: ChangeKind::Deleted,
+                start: *position,
+                end: position + nucleotides.len(),
+            },
+        });
+        self.dirty_region = Some(DirtyRegion::extend(self.dirty_region, operation));
+        self.sequence_dirty = true;
+    }
+
+    /// Display codons for the codon indices `first..end`.
+    fn display_codons(&self, first: usize, end: usize) -> Vec<DisplayCodon> {
+        (first..end)
+            .map(|index| {
+                let chunk = self
+                    .sequence
+                    .range(index * 3..(index * 3 + 3).min(self.sequence.len()))
+                    .collect::<Vec<_>>();
+                DisplayCodon::new(&chunk)
+            })
+            .collect()
+    }
+
+    /// Applies an operation to the sequence without recording it.
+    fn apply_operation(&mut self, operation: &EditOperation) {
+        self.mark_dirty(operation);
+        match operation {
+            EditOperation::Insert {
+                position,
+                nucleotides,
+                lowercase,
+            } => {
+                self.sequence
+                    .insert_packed(*position, nucleotides, lowercase);
+            }
+            EditOperation::Delete {
+                position,
+                nucleotides,
+                ..
+            } => {
+                self.sequence
+                    .remove(*position..*position + nucleotides.len());
+            }
+        }
+    }
+
+    fn apply_history_step(&mut self, step: HistoryStep) {
+        match step {
+            HistoryStep::Undo(entry) => {
+                for operation in entry.operations.iter().rev() {
+                    self.apply_operation(&operation.inverted());
+                }
+                self.cursor_pos = entry.cursor_before;
+                self.selection = entry.selection_before;
+            }
+            HistoryStep::Redo(entry) => {
+                for operation in entry.operations.iter() {
+                    self.apply_operation(operation);
+                }
+                self.cursor_pos = entry.cursor_after;
+                self.selection = entry.selection_after;
+            }
         }
+    }
+
+    /// Applies an operation to the sequence and records it as part of the current edit.
+    fn inner_apply_operation(&mut self, operation: EditOperation) {
+        self.apply_operation(&operation);
+        self.history.record(operation);
+    }
+
+    fn inner_remove_range(&mut self, start: usize, end: usize) {
+        self.inner_apply_operation(EditOperation::Delete {
+            position: start,
+            nucleotides: self.sequence.packed(start..end),
+            lowercase: self.sequence.lowercase(start..end),
+        });
+    }
+
+    #[inline]
+    fn inner_insert_nucleotide(&mut self, nucleotide: IupacNucleotide, lowercase: bool) {
+        self.inner_apply_operation(EditOperation::Insert {
+            position: self.cursor_pos,
+            nucleotides: PackedNucleotides::from_nucleotides(&[nucleotide]),
+            lowercase: CaseMask::from_lowercase([lowercase]),
+        });
         self.inner_move_cursor(CursorMovement::By(1), true);
     }
 
-    fn inner_insert_multiple_nucleotides(&mut self, nucleotides: &[IupacNucleotide]) {
-        let mut vec = self.sequence.iter().cloned().collect::<Vec<_>>();
-        vec.splice(
-            self.cursor_pos..self.cursor_pos,
-            nucleotides.iter().cloned(),
-        );
-        self.sequence = VecDeque::from(vec);
+    fn inner_insert_multiple_nucleotides(
+        &mut self,
+        nucleotides: &[IupacNucleotide],
+        lowercase: &CaseMask,
+    ) {
+        self.inner_apply_operation(EditOperation::Insert {
+            position: self.cursor_pos,
+            nucleotides: PackedNucleotides::from_nucleotides(nucleotides),
+            lowercase: lowercase.clone(),
+        });
         self.inner_move_cursor(CursorMovement::By(nucleotides.len() as isize), true);
     }
 
@@ -215,15 +379,94 @@ impl Editor {
         }
     }
 
+    /// Finds the non-overlapping occurrences of a motif which can be replaced.
+    fn inner_find_replaceable(&self, motif: &Motif, strand_aware: bool) -> Vec<SearchHit> {
+        let mut hits = Vec::new();
+        for hit in self.find_motif(motif, false) {
+            if !strand_aware && hit.strand == Strand::Reverse {
+                continue;
+            }
+            match hits.last() {
+                Some(SearchHit { end, .. }) if hit.start < *end => (),
+                _ => hits.push(hit),
+            }
+        }
+        hits
+    }
+
+    /// Replaces ordered, non-overlapping hits. Hits on the reverse strand are
+    /// replaced with the reverse complement of the replacement.
+    fn inner_replace_hits(
+        &mut self,
+        hits: &[SearchHit],
+        replacement: &[IupacNucleotide],
+        lowercase: &CaseMask,
+    ) {
+        let reverse_replacement = replacement
+            .iter()
+            .rev()
+            .map(|n| n.complement())
+            .collect::<Vec<_>>();
+        let reverse_lowercase = CaseMask::from_lowercase(
+            (0..replacement.len())
+                .rev()
+                .map(|position| lowercase.is_lowercase(position)),
+        );
+        let replacement_for = |strand| match strand {
+            Strand::Forward => (replacement, lowercase),
+            Strand::Reverse => (reverse_replacement.as_slice(), &reverse_lowercase),
+        };
+
+        // Recorded back to front, so that every position is still valid when applied in order
+        for hit in hits.iter().rev() {
+            let (nucleotides, lowercase) = replacement_for(hit.strand);
+            let operations = [
+                EditOperation::Delete {
+                    position: hit.start,
+                    nucleotides: self.sequence.packed(hit.start..hit.end),
+                    lowercase: self.sequence.lowercase(hit.start..hit.end),
+                },
+                EditOperation::Insert {
+                    position: hit.start,
+                    nucleotides: PackedNucleotides::from_nucleotides(nucleotides),
+                    lowercase: lowercase.clone(),
+                },
+            ];
+            for operation in operations {
+                self.mark_dirty(&operation);
+                self.history.record(operation);
+            }
+        }
+
+        // Rebuild the sequence in a single pass instead of splicing every hit
+        let mut sequence = Vec::with_capacity(self.sequence.len());
+        let mut sequence_lowercase = CaseMask::default();
+        let mut position = 0;
+        for hit in hits {
+            sequence_lowercase.append(
+                sequence.len(),
+                &self.sequence.lowercase(position..hit.start),
+            );
+            sequence.extend(self.sequence.range(position..hit.start));
+            let (nucleotides, lowercase) = replacement_for(hit.strand);
+            sequence_lowercase.append(sequence.len(), lowercase);
+            sequence.extend(nucleotides.iter().cloned());
+            position = hit.end;
+        }
+        let end = self.sequence.len();
+        sequence_lowercase.append(sequence.len(), &self.sequence.lowercase(position..end));
+        sequence.extend(self.sequence.range(position..end));
+        self.sequence = Sequence::from_nucleotides(&sequence, &sequence_lowercase);
+    }
+
     fn inner_reset_selection(&mut self) {
         self.selection = None;
     }
 
     fn inner_delete_selection_content(&mut self) {
-        if let Some(selection) = &self.selection {
-            self.sequence.drain(selection.start..selection.end);
+        if let Some(selection) = self.selection.clone() {
+            self.inner_remove_range(selection.start, selection.end);
             self.cursor_pos = selection.start.min(self.sequence.len()).max(0);
-            self.sequence_dirty = true;
             self.inner_reset_selection();
         }
     }
@@ -235,150 +478,480 @@ impl Editor {
 
 impl Editor {
     pub fn reset(&mut self) {
+        self.changes.clear();
+        if !self.sequence.is_empty() {
+            self.changes.push(SequenceChange {
+                kind: ChangeKind::Deleted,
+                start: 0,
+                end: self.sequence.len(),
+            });
+        }
         self.sequence.clear();
         self.codons.clear();
         self.cursor_pos = 0;
         self.selection = None;
         self.sequence_dirty = false;
+        self.dirty_region = None;
         self.history.clear();
-        self.history.push(self.snapshot());
+        self.checkpoints.clear();
     }
 
-    pub fn insert(&mut self, letter: char) {
-        self.inner_delete_selection_content();
+    /// Replaces the content with a sequence, restoring its saved history if it matches.
+    ///
+    /// Returns `false` if a history was given but had to be dropped because it
+    /// doesn't belong to the sequence. The content is kept if the sequence is invalid.
+    pub fn load(
+        &mut self,
+        sequence: String,
+        history: Option<SavedHistory>,
+    ) -> Result<bool, EditorError> {
+        let (nucleotides, lowercase) = parse_nucleotides(&sequence)?;
+        self.reset();
+        self.begin_edit();
+        self.inner_insert_multiple_nucleotides(&nucleotides, &lowercase);
+        self.commit_edit();
+        Ok(match history {
+            Some(history) => self.history.load(history, &self.sequence),
+            None => true,
+        })
+    }
 
-        if let Ok(nucleotide) = IupacNucleotide::try_from_letter(letter) {
-            self.inner_insert_nucleotide(nucleotide);
-            self.sequence_dirty = true;
-        }
+    pub fn insert(&mut self, letter: char) -> Result<(), EditorError> {
+        let nucleotide = IupacNucleotide::try_from_letter(letter)
+            .map_err(|_| EditorError::InvalidLetter { character: letter })?;
 
-        self.history.push(self.snapshot());
+        self.begin_edit();
+        self.inner_delete_selection_content();
+        self.inner_insert_nucleotide(nucleotide, letter.is_ascii_lowercase());
+        self.commit_typing_edit();
+        Ok(())
     }
 
-    pub fn insert_all(&mut self, text: String) {
+    /// Replaces the selection with IUPAC letters, ignoring whitespace.
+    ///
+    /// Nothing is inserted if the text contains any other character.
+    #[cfg(test)]
+    pub fn insert_all(&mut self, text: String) -> Result<(), EditorError> {
+        let (nucleotides, lowercase) = parse_nucleotides(&text)?;
+
+        self.begin_edit();
         self.inner_delete_selection_content();
+        self.inner_insert_multiple_nucleotides(&nucleotides, &lowercase);
+        self.commit_edit();
+        Ok(())
+    }
 
-        if let Ok(nucleotides) = text
-            .chars()
-            .map(|c| IupacNucleotide::try_from_letter(c))
-            .collect::<Result<Vec<_>, _>>()
-        {
-            self.inner_insert_multiple_nucleotides(&nucleotides);
-            self.sequence_dirty = true;
+    /// Deletes the selection, or the nucleotide before the cursor.
+    pub fn delete(&mut self) -> Result<(), EditorError> {
+        if self.selection.is_none() && self.cursor_pos == 0 {
+            return Err(EditorError::NothingToDelete);
         }
+        self.begin_edit();
 
-        self.history.push(self.snapshot());
-    }
-
-    pub fn delete(&mut self) {
         if self.selection.is_some() {
             self.inner_delete_selection_content();
-            self.history.push(self.snapshot());
-            return;
-        }
-
-        match self.cursor_pos {
-            0 => (),
-            1 => _ = self.sequence.pop_front(),
-            i if i == self.sequence.len() => _ = self.sequence.pop_back(),
-            i => _ = self.sequence.remove(i - 1),
-        }
-
-        if self.cursor_pos != 0 {
-            self.sequence_dirty = true;
+            self.commit_edit();
+            return Ok(());
         }
 
+        self.inner_remove_range(self.cursor_pos - 1, self.cursor_pos);
         self.inner_move_cursor(CursorMovement::By(-1), true);
-        self.history.push(self.snapshot());
+        self.commit_typing_edit();
+        Ok(())
     }
 
-    pub fn delete_next(&mut self) {
+    pub fn delete_next(&mut self) -> Result<(), EditorError> {
+        if self.selection.is_none() && self.cursor_pos >= self.sequence.len() {
+            return Err(EditorError::NothingToDelete);
+        }
+        self.begin_edit();
         self.inner_delete_selection_content();
 
-        match self.cursor_pos {
-            0 => _ = self.sequence.pop_front(),
-            i if i == self.sequence.len() - 1 => _ = self.sequence.pop_back(),
-            i if i == self.sequence.len() => (),
-            i => _ = self.sequence.remove(i),
+        if self.cursor_pos < self.sequence.len() {
+            self.inner_remove_range(self.cursor_pos, self.cursor_pos + 1);
         }
-        self.sequence_dirty = true;
 
-        self.history.push(self.snapshot());
+        self.commit_edit();
+        Ok(())
     }
 
-    pub fn move_cursor(&mut self, movement: CursorMovement) {
+    pub fn move_cursor(&mut self, movement: CursorMovement) -> Result<(), EditorError> {
+        if let CursorMovement::To(index) = movement {
+            self.check_index(index)?;
+        }
         self.inner_move_cursor(movement, true);
-
-        // self.history.push(self.snapshot());
+        self.history.seal();
+        Ok(())
     }
 
-    pub fn move_selection(&mut self, movement: SelectionMovement) {
+    pub fn move_selection(&mut self, movement: SelectionMovement) -> Result<(), EditorError> {
+        if let SelectionMovement::Set { start, end } = movement {
+            self.check_index(start.max(end))?;
+        }
         self.inner_move_selection(movement);
-
-        // self.history.push(self.snapshot());
+        self.history.seal();
+        Ok(())
     }
 
-    // TODO: This could be heavily optimized by keeping track of "dirty" coding regions
-    // and recoding only the changed areas into display codons.
+    /// Recomputes the display codons which changed since the last update.
+    ///
+    /// If the length changed by a multiple of three, the codons behind the
+    /// changes keep their frame and only the changed codons are recomputed.
+    /// Otherwise everything from the first changed codon onward is.
     pub fn update(&mut self) {
-        self.codons = {
-            let mut display_codons = Vec::new();
-            let mut chunk = Vec::with_capacity(3);
-            for nucleotide in self.sequence.iter().cloned() {
-                chunk.push(nucleotide);
-                if chunk.len() == 3 {
-                    display_codons.push(DisplayCodon::new(&chunk));
-                    chunk.clear();
-                }
+        let codon_count = (self.sequence.len() + 2) / 3;
+        match self.dirty_region.take() {
+            Some(region) if region.delta % 3 == 0 => {
+                let first = region.start / 3;
+                let end = ((region.end + 2) / 3).min(codon_count).max(first);
+                let old_end = (end as isize - region.delta / 3) as usize;
+                let codons = self.display_codons(first, end);
+                self.codons.splice(first..old_end, codons);
             }
-            if !chunk.is_empty() {
-                display_codons.push(DisplayCodon::new(&chunk));
+            Some(region) => {
+                let first = region.start / 3;
+                self.codons.truncate(first);
+                let codons = self.display_codons(first, codon_count);
+                self.codons.extend(codons);
             }
-            display_codons
-        };
+            None => {}
+        }
+        debug_assert_eq!(self.codons.len(), codon_count);
         self.sequence_dirty = false;
     }
 
-    pub fn get_selected_sequence(&self) -> String {
-        use plasmid::traits::ToLetter;
+    /// Sequence items for the codons overlapping the nucleotide window `start..end`.
+    ///
+    /// The display codons have to be up to date, see [`Editor::update`].
+    ///
+    /// Returns the index of the first nucleotide of the first item along with the items.
+    pub fn sequence_items(&self, start: usize, end: usize, packed: bool) -> (usize, SequenceItems) {
+        let first = (start / 3).min(self.codons.len());
+        let last = (end.saturating_add(2) / 3).clamp(first, self.codons.len());
+        let codons = &self.codons[first..last];
+        let items = if packed {
+            SequenceItems::Packed(PackedSequenceItems {
+                start_index: first * 3,
+                codons: codons.iter().flat_map(|codon| &codon.nucleotides).collect(),
+                anticodons: codons
+                    .iter()
+                    .flat_map(|codon| &codon.anti_nucleotides)
+                    .collect(),
+                peptides: codons
+                    .iter()
+                    .map(|codon| codon.peptide.unwrap_or(' '))
+                    .collect(),
+            })
+        } else {
+            SequenceItems::Items(
+                codons
+                    .iter()
+                    .enumerate()
+                    .map(|(offset, codon)| SequenceItem {
+                        codon: codon.nucleotides.clone(),
+                        anticodon: codon.anti_nucleotides.clone(),
+                        peptide: codon.peptide,
+                        start_index: (first + offset) * 3,
+                    })
+                    .collect(),
+            )
+        };
+        (first * 3, items)
+    }
 
+    pub fn take_changes(&mut self) -> Vec<SequenceChange> {
+        std::mem::take(&mut self.changes)
+    }
+
+    pub fn get_selected_sequence(&self) -> String {
         match &self.selection {
-            Some(selection) => self
-                .sequence
-                .range(selection.start..selection.end)
-                .map(|nuc| nuc.to_letter())
-                .collect(),
+            Some(selection) => self.sequence.letters(selection.start..selection.end),
             None => String::default(),
         }
     }
 
-    pub fn undo(&mut self) {
-        if let Some(snapshot) = self.history.get_undo_snapshot() {
-            self.apply_snapshot(snapshot);
+    /// The selection and its nucleotides as read on the given strand.
+    pub fn get_selected_strand(&self, strand: Strand) -> Option<(Selection, String)> {
+        let selection = self.selection.clone()?;
+        let text = match strand {
+            Strand::Forward => self.sequence.letters(selection.start..selection.end),
+            Strand::Reverse => {
+                let lowercase = self.sequence.lowercase(selection.start..selection.end);
+                self.sequence
+                    .range(selection.start..selection.end)
+                    .rev()
+                    .zip((0..selection.end - selection.start).rev())
+                    .map(|(nuc, offset)| {
+                        cased_letter(&nuc.complement(), lowercase.is_lowercase(offset))
+                    })
+                    .collect()
+            }
+        };
+        Some((selection, text))
+    }
+
+    /// Deletes the selection, returning the history node of the edit.
+    pub fn cut(&mut self) -> Result<NodeId, EditorError> {
+        if self.selection.is_none() {
+            return Err(EditorError::EmptySelection);
+        }
+        self.begin_edit();
+        self.inner_delete_selection_content();
+        self.commit_edit();
+        Ok(self.history.current())
+    }
+
+    /// Replaces the selection with nucleotides. If `cut_node` is the current
+    /// history node, the paste is undone together with that cut.
+    ///
+    /// Returns the position the nucleotides were inserted at.
+    pub fn paste(
+        &mut self,
+        nucleotides: &[IupacNucleotide],
+        lowercase: &CaseMask,
+        cut_node: Option<NodeId>,
+    ) -> usize {
+        self.begin_edit();
+        self.inner_delete_selection_content();
+        let position = self.cursor_pos;
+        self.inner_insert_multiple_nucleotides(nucleotides, lowercase);
+        match cut_node {
+            Some(node) => {
+                self.history
+                    .commit_appending(node, self.cursor_pos, self.selection.clone())
+            }
+            None => self.commit_edit(),
+        }
+        position
+    }
+
+    pub fn find_motif(&self, motif: &Motif, circular: bool) -> Vec<SearchHit> {
+        motif.find_all(&self.sequence.search_index(), circular)
+    }
+
+    pub fn find_approximate(
+        &self,
+        motif: &Motif,
+        circular: bool,
+        metric: DistanceMetric,
+        max_distance: usize,
+    ) -> Vec<ApproximateHit> {
+        motif.find_approximate(
+            &self.sequence.search_index(),
+            circular,
+            metric,
+            max_distance,
+        )
+    }
+
+    pub fn find_peptide(&self, peptide: &Peptide) -> Vec<SearchHit> {
+        peptide.find_all(&self.sequence.search_index())
+    }
+
+    pub fn find_pattern(&self, pattern: &Pattern, target: PatternTarget) -> Vec<SearchHit> {
+        pattern.find_all(&self.sequence.search_index(), target)
+    }
+
+    /// Replaces the next occurrence at or after the cursor, wrapping around
+    /// to the start of the sequence. The replaced range is selected.
+    ///
+    /// Returns the location of the inserted replacement.
+    pub fn replace_next(
+        &mut self,
+        motif: &Motif,
+        replacement: &str,
+        strand_aware: bool,
+    ) -> Result<Option<SearchHit>, EditorError> {
+        let (replacement, lowercase) = parse_nucleotides(replacement)?;
+        let hits = self.inner_find_replaceable(motif, strand_aware);
+        let hit = match hits
+            .iter()
+            .find(|hit| hit.start >= self.cursor_pos)
+            .or_else(|| hits.first())
+        {
+            Some(hit) => hit.clone(),
+            None => return Ok(None),
+        };
+
+        self.begin_edit();
+        self.inner_replace_hits(std::slice::from_ref(&hit), &replacement, &lowercase);
+        let end = hit.start + replacement.len();
+        self.inner_move_cursor(CursorMovement::To(hit.start), true);
+        self.inner_move_selection(SelectionMovement::Set {
+            start: hit.start,
+            end,
+        });
+
+        self.commit_edit();
+        Ok(Some(SearchHit { end, ..hit }))
+    }
+
+    /// Replaces every occurrence as a single undo step.
+    ///
+    /// Returns the number of replaced occurrences.
+    pub fn replace_all(
+        &mut self,
+        motif: &Motif,
+        replacement: &str,
+        strand_aware: bool,
+    ) -> Result<usize, EditorError> {
+        let (replacement, lowercase) = parse_nucleotides(replacement)?;
+        let hits = self.inner_find_replaceable(motif, strand_aware);
+        if hits.is_empty() {
+            return Ok(0);
+        }
+
+        // Every replacement before the cursor moves it by its change in length.
+        // A cursor within a replaced range stays within the replacement.
+        let mut cursor_pos = self.cursor_pos;
+        for hit in hits.iter().filter(|hit| hit.start < self.cursor_pos) {
+            let replaced = (self.cursor_pos - hit.start).min(hit.end - hit.start);
+            let inserted = if hit.end <= self.cursor_pos {
+                replacement.len()
+            } else {
+                replaced.min(replacement.len())
+            };
+            cursor_pos = cursor_pos - replaced + inserted;
+        }
+
+        self.begin_edit();
+        self.inner_replace_hits(&hits, &replacement, &lowercase);
+        self.inner_move_cursor(CursorMovement::To(cursor_pos), true);
+
+        self.commit_edit();
+        Ok(hits.len())
+    }
+
+    pub fn create_checkpoint(&mut self, name: String) -> CheckpointData {
+        let snapshot = self.snapshot();
+        self.checkpoints.create(name, snapshot).into()
+    }
+
+    /// Replaces the checkpoints with saved ones.
+    ///
+    /// Returns `false` if they are malformed, in which case they are dropped.
+    pub fn restore_checkpoints(&mut self, checkpoints: Checkpoints) -> bool {
+        if !checkpoints.is_well_formed() {
+            return false;
         }
+        self.checkpoints = checkpoints;
+        true
     }
 
-    pub fn redo(&mut self) {
-        if let Some(snapshot) = self.history.get_redo_snapshot() {
-            self.apply_snapshot(snapshot);
+    /// Changes the sequence back to a checkpoint as a single undoable edit.
+    pub fn restore_checkpoint(&mut self, id: usize) -> Result<(), EditorError> {
+        let snapshot = match self.checkpoints.get(id) {
+            Some(checkpoint) => checkpoint.snapshot.clone(),
+            None => return Err(EditorError::UnknownCheckpoint { id }),
+        };
+
+        self.begin_edit();
+        // Applied back to front, so that the positions of earlier hunks stay valid.
+        // Case-only changes are replaced like any other change.
+        for hunk in diff_sequences(&self.sequence, &snapshot.sequence)
+            .iter()
+            .rev()
+        {
+            if hunk.old_end > hunk.old_start {
+                self.inner_remove_range(hunk.old_start, hunk.old_end);
+            }
+            if hunk.new_end > hunk.new_start {
+                self.inner_apply_operation(EditOperation::Insert {
+                    position: hunk.old_start,
+                    nucleotides: snapshot.sequence.packed(hunk.new_start..hunk.new_end),
+                    lowercase: snapshot.sequence.lowercase(hunk.new_start..hunk.new_end),
+                });
+            }
         }
+        self.cursor_pos = snapshot.cursor_pos;
+        self.selection = snapshot.selection;
+        self.commit_edit();
+        Ok(())
+    }
+
+    /// Compares a checkpoint to the current sequence.
+    pub fn diff_checkpoint(&self, id: usize) -> Result<Vec<DiffHunk>, EditorError> {
+        let checkpoint = self
+            .checkpoints
+            .get(id)
+            .ok_or(EditorError::UnknownCheckpoint { id })?;
+        Ok(diff_sequences(
+            &checkpoint.snapshot.sequence,
+            &self.sequence,
+        ))
+    }
+
+    pub fn undo(&mut self) -> Result<(), EditorError> {
+        self.history.seal();
+        let entry = self
+            .history
+            .get_undo_entry()
+            .ok_or(EditorError::NothingToUndo)?;
+        self.apply_history_step(HistoryStep::Undo(entry));
+        Ok(())
+    }
+
+    pub fn redo(&mut self) -> Result<(), EditorError> {
+        self.history.seal();
+        let entry = self
+            .history
+            .get_redo_entry()
+            .ok_or(EditorError::NothingToRedo)?;
+        self.apply_history_step(HistoryStep::Redo(entry));
+        Ok(())
+    }
+
+    /// Restores the state of any node in the undo tree, including abandoned branches.
+    pub fn jump_to_history_node(&mut self, id: NodeId) -> Result<(), EditorError> {
+        let steps = self
+            .history
+            .jump_to(id)
+            .ok_or(EditorError::UnknownHistoryNode { id })?;
+        for step in steps {
+            self.apply_history_step(step);
+        }
+        self.history.seal();
+        Ok(())
     }
 }
 
 // #endregion
 
+/// Parses IUPAC letters, ignoring whitespace, and remembers which of them
+/// were lowercase. Fails on the first other character.
+pub fn parse_nucleotides(text: &str) -> Result<(Vec<IupacNucleotide>, CaseMask), EditorError> {
+    let mut nucleotides = Vec::new();
+    let mut lowercase = Vec::new();
+    for (position, character) in text.chars().enumerate() {
+        if character.is_whitespace() {
+            continue;
+        }
+        let nucleotide = IupacNucleotide::try_from_letter(character).map_err(|_| {
+            EditorError::InvalidCharacter {
+                character,
+                position,
+            }
+        })?;
+        nucleotides.push(nucleotide);
+        lowercase.push(character.is_ascii_lowercase());
+    }
+    Ok((nucleotides, CaseMask::from_lowercase(lowercase)))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::history::EditorSnapshot;
+    use crate::{
+        case_mask::CaseMask, error::EditorError, history::EditOperation, packed::PackedNucleotides,
+    };
 
-    use super::{Editor, SelectionMovement};
+    use super::{CursorMovement, Editor, Selection, SelectionMovement};
 
     #[test]
     fn test_insert() {
         use plasmid::prelude::IupacNucleotide::*;
 
         let mut state = Editor::default();
-        state.insert('A');
+        state.insert('A').unwrap();
         assert_eq!(state.sequence, [A]);
     }
 
@@ -387,11 +960,13 @@ mod tests {
         use plasmid::prelude::IupacNucleotide::*;
 
         let mut state = Editor::default();
-        state.insert('A');
-        state.insert('C');
-        state.insert('G');
-        state.move_selection(SelectionMovement::Set { start: 1, end: 2 });
-        state.insert('T');
+        state.insert('A').unwrap();
+        state.insert('C').unwrap();
+        state.insert('G').unwrap();
+        state
+            .move_selection(SelectionMovement::Set { start: 1, end: 2 })
+            .unwrap();
+        state.insert('T').unwrap();
         assert_eq!(state.sequence, [A, T, G])
     }
 
@@ -400,7 +975,7 @@ mod tests {
         use plasmid::prelude::IupacNucleotide::*;
 
         let mut state = Editor::default();
-        state.insert_all("ACGT".to_string());
+        state.insert_all("ACGT".to_string()).unwrap();
         assert_eq!(state.sequence, [A, C, G, T]);
     }
 
@@ -409,27 +984,75 @@ mod tests {
         use plasmid::prelude::IupacNucleotide::*;
 
         let mut state = Editor::default();
-        state.insert_all("ACCCT".to_string());
-        state.move_selection(SelectionMovement::Set { start: 1, end: 5 });
-        state.insert_all("TG".to_string());
+        state.insert_all("ACCCT".to_string()).unwrap();
+        state
+            .move_selection(SelectionMovement::Set { start: 1, end: 5 })
+            .unwrap();
+        state.insert_all("TG".to_string()).unwrap();
         assert_eq!(state.sequence, [A, T, G]);
 
         let mut state = Editor::default();
-        state.insert_all("ACCCT".to_string());
-        state.move_selection(SelectionMovement::Set { start: 0, end: 5 });
-        state.insert_all("TG".to_string());
+        state.insert_all("ACCCT".to_string()).unwrap();
+        state
+            .move_selection(SelectionMovement::Set { start: 0, end: 5 })
+            .unwrap();
+        state.insert_all("TG".to_string()).unwrap();
         assert_eq!(state.sequence, [T, G]);
     }
 
+    #[test]
+    fn test_invalid_input_is_rejected() {
+        use super::CursorMovement;
+        use plasmid::prelude::IupacNucleotide::*;
+
+        let mut state = Editor::default();
+        state.insert_all("ACGT".to_string()).unwrap();
+        state
+            .move_selection(SelectionMovement::Set { start: 1, end: 3 })
+            .unwrap();
+
+        assert_eq!(
+            state.insert_all("GG AXC".to_string()),
+            Err(EditorError::InvalidCharacter {
+                character: 'X',
+                position: 4
+            })
+        );
+        assert_eq!(
+            state.insert('J'),
+            Err(EditorError::InvalidLetter { character: 'J' })
+        );
+        // The selection isn't replaced by a failed insertion
+        assert_eq!(state.sequence, [A, C, G, T]);
+        assert_eq!(state.selection, Some(Selection { start: 1, end: 3 }));
+
+        assert_eq!(
+            state.move_cursor(CursorMovement::To(5)),
+            Err(EditorError::IndexOutOfRange { index: 5, len: 4 })
+        );
+        assert_eq!(
+            state.move_selection(SelectionMovement::Set { start: 2, end: 9 }),
+            Err(EditorError::IndexOutOfRange { index: 9, len: 4 })
+        );
+        assert_eq!(state.cursor_pos, 3);
+
+        state.move_selection(SelectionMovement::Reset).unwrap();
+        assert_eq!(state.cut(), Err(EditorError::EmptySelection));
+    }
+
     #[test]
     fn test_delete() {
         use plasmid::prelude::IupacNucleotide::*;
 
         let mut state = Editor::default();
-        state.insert_all("ACGT".to_string());
-        state.delete();
+        state.insert_all("ACGT".to_string()).unwrap();
+        state.delete().unwrap();
         assert_eq!(state.sequence, [A, C, G]);
-        state.delete();
+        state.delete().unwrap();
+        assert_eq!(state.sequence, [A, C]);
+
+        state.cursor_pos = 0;
+        assert_eq!(state.delete(), Err(EditorError::NothingToDelete));
         assert_eq!(state.sequence, [A, C]);
     }
 
@@ -438,9 +1061,11 @@ mod tests {
         use plasmid::prelude::IupacNucleotide::*;
 
         let mut state = Editor::default();
-        state.insert_all("ACGT".to_string());
-        state.move_selection(SelectionMovement::Set { start: 1, end: 3 });
-        state.delete();
+        state.insert_all("ACGT".to_string()).unwrap();
+        state
+            .move_selection(SelectionMovement::Set { start: 1, end: 3 })
+            .unwrap();
+        state.delete().unwrap();
         assert_eq!(state.sequence, [A, T]);
     }
 
@@ -449,13 +1074,13 @@ mod tests {
         use plasmid::prelude::IupacNucleotide::*;
 
         let mut state = Editor::default();
-        state.insert_all("ACGT".to_string());
-        state.delete_next();
+        state.insert_all("ACGT".to_string()).unwrap();
+        assert_eq!(state.delete_next(), Err(EditorError::NothingToDelete));
         assert_eq!(state.sequence, [A, C, G, T]);
         state.cursor_pos = 0;
-        state.delete_next();
+        state.delete_next().unwrap();
         assert_eq!(state.sequence, [C, G, T]);
-        state.delete_next();
+        state.delete_next().unwrap();
         assert_eq!(state.sequence, [G, T]);
     }
 
@@ -464,56 +1089,479 @@ mod tests {
         use super::CursorMovement;
 
         let mut state = Editor::default();
-        state.insert_all("ACGT".to_string());
+        state.insert_all("ACGT".to_string()).unwrap();
 
-        state.move_cursor(CursorMovement::To(4));
+        state.move_cursor(CursorMovement::To(4)).unwrap();
         assert_eq!(state.cursor_pos, 4);
 
-        state.move_cursor(CursorMovement::To(0));
+        state.move_cursor(CursorMovement::To(0)).unwrap();
         assert_eq!(state.cursor_pos, 0);
 
-        state.move_cursor(CursorMovement::By(1));
+        state.move_cursor(CursorMovement::By(1)).unwrap();
         assert_eq!(state.cursor_pos, 1);
 
-        state.move_cursor(CursorMovement::By(2));
+        state.move_cursor(CursorMovement::By(2)).unwrap();
         assert_eq!(state.cursor_pos, 3);
 
-        state.move_cursor(CursorMovement::By(-1));
+        state.move_cursor(CursorMovement::By(-1)).unwrap();
         assert_eq!(state.cursor_pos, 2);
 
-        state.move_cursor(CursorMovement::CodonStart);
+        state.move_cursor(CursorMovement::CodonStart).unwrap();
         assert_eq!(state.cursor_pos, 0);
 
-        state.move_cursor(CursorMovement::CodonEnd);
+        state.move_cursor(CursorMovement::CodonEnd).unwrap();
         assert_eq!(state.cursor_pos, 3);
 
-        state.move_cursor(CursorMovement::Start);
+        state.move_cursor(CursorMovement::Start).unwrap();
         assert_eq!(state.cursor_pos, 0);
 
-        state.move_cursor(CursorMovement::End);
+        state.move_cursor(CursorMovement::End).unwrap();
         assert_eq!(state.cursor_pos, 4);
     }
 
+    #[test]
+    fn test_replace_next() {
+        use super::CursorMovement;
+        use super::IupacNucleotide::{A, C, G, T};
+        use crate::search::Motif;
+
+        let mut state = Editor::default();
+        state.insert_all("GGACCACCT".to_string()).unwrap();
+        state.move_cursor(CursorMovement::Start).unwrap();
+
+        let motif = Motif::parse("ACC").unwrap();
+        let hit = state.replace_next(&motif, "T", false).unwrap().unwrap();
+        assert_eq!((hit.start, hit.end), (2, 3));
+        assert_eq!(state.sequence, [G, G, T, A, C, C, T]);
+        assert_eq!(state.selection, Some(Selection { start: 2, end: 3 }));
+
+        state.replace_next(&motif, "T", false).unwrap();
+        assert_eq!(state.sequence, [G, G, T, T, T]);
+        assert_eq!(state.replace_next(&motif, "T", false), Ok(None));
+    }
+
+    #[test]
+    fn test_replace_all() {
+        use super::IupacNucleotide::{A, C, G, T};
+        use crate::search::Motif;
+
+        let mut state = Editor::default();
+        state.insert_all("AACGTTGG".to_string()).unwrap();
+
+        // AAC on the forward strand, GTT as its reverse complement
+        let motif = Motif::parse("AAC").unwrap();
+        assert_eq!(state.replace_all(&motif, "GG", false), Ok(1));
+        assert_eq!(state.sequence, [G, G, G, T, T, G, G]);
+
+        state.undo().unwrap();
+        assert_eq!(state.replace_all(&motif, "GG", true), Ok(2));
+        assert_eq!(state.sequence, [G, G, C, C, G, G]);
+
+        state.undo().unwrap();
+        assert_eq!(state.sequence, [A, A, C, G, T, T, G, G]);
+    }
+
+    #[test]
+    fn test_replace_all_shifts_cursor() {
+        use crate::search::Motif;
+
+        let mut state = Editor::default();
+        state.insert_all("AACTTAACTTAACTT".to_string()).unwrap();
+        let motif = Motif::parse("AAC").unwrap();
+
+        // Behind two occurrences which grow by two each
+        state.move_cursor(CursorMovement::To(11)).unwrap();
+        assert_eq!(state.replace_all(&motif, "GGGGG", false), Ok(3));
+        assert_eq!(state.cursor_pos, 15);
+        assert_eq!(state.sequence.letters(15..16), "G");
+
+        // Within an occurrence which shrinks, the cursor stays within the replacement
+        state.undo().unwrap();
+        state.move_cursor(CursorMovement::To(8)).unwrap();
+        assert_eq!(state.replace_all(&motif, "G", false), Ok(3));
+        assert_eq!(state.cursor_pos, 4);
+        assert_eq!(state.sequence.letters(0..state.sequence.len()), "GTTGTTGTT");
+    }
+
     #[test]
     fn test_undo_redo() {
         use super::IupacNucleotide::{A, C, G, T};
 
         let mut state = Editor::default();
-        state.insert_all("ACGT".to_string());
+        state.insert_all("ACGT".to_string()).unwrap();
 
         assert_eq!(
-            state.history.peek_undo_snapshot(),
-            Some(&EditorSnapshot::default())
+            state.history.peek_undo_entry().unwrap().operations,
+            [EditOperation::Insert {
+                position: 0,
+                nucleotides: PackedNucleotides::from_nucleotides(&[A, C, G, T]),
+                lowercase: CaseMask::default(),
+            }]
         );
 
-        state.undo();
+        state.undo().unwrap();
         assert!(state.sequence.is_empty());
         assert_eq!(state.cursor_pos, 0);
         assert_eq!(state.selection, None);
 
-        state.redo();
+        assert_eq!(state.undo(), Err(EditorError::NothingToUndo));
+
+        state.redo().unwrap();
         assert_eq!(state.sequence, [A, C, G, T]);
         assert_eq!(state.cursor_pos, 4);
         assert_eq!(state.selection, None);
+        assert_eq!(state.redo(), Err(EditorError::NothingToRedo));
+    }
+
+    #[test]
+    fn test_undo_redo_delete() {
+        use super::IupacNucleotide::{A, C, G, T};
+
+        let mut state = Editor::default();
+        state.insert_all("ACGT".to_string()).unwrap();
+        state
+            .move_selection(SelectionMovement::Set { start: 1, end: 3 })
+            .unwrap();
+        state.delete().unwrap();
+        state.delete().unwrap();
+        assert_eq!(state.sequence, [T]);
+
+        state.undo().unwrap();
+        assert_eq!(state.sequence, [A, T]);
+        assert_eq!(state.cursor_pos, 1);
+
+        state.undo().unwrap();
+        assert_eq!(state.sequence, [A, C, G, T]);
+        assert_eq!(state.selection, Some(Selection { start: 1, end: 3 }));
+
+        state.redo().unwrap();
+        state.redo().unwrap();
+        assert_eq!(state.sequence, [T]);
+        assert_eq!(state.cursor_pos, 0);
+    }
+
+    #[test]
+    fn test_undo_typing() {
+        use super::CursorMovement;
+        use super::IupacNucleotide::{A, C, G, T};
+
+        let mut state = Editor::default();
+        for letter in "ACGT".chars() {
+            state.insert(letter).unwrap();
+        }
+        state.move_cursor(CursorMovement::To(2)).unwrap();
+        state.insert('T').unwrap();
+        state.insert('T').unwrap();
+        assert_eq!(state.sequence, [A, C, T, T, G, T]);
+
+        state.undo().unwrap();
+        assert_eq!(state.sequence, [A, C, G, T]);
+        assert_eq!(state.cursor_pos, 2);
+
+        state.undo().unwrap();
+        assert!(state.sequence.is_empty());
+    }
+
+    #[test]
+    fn test_jump_to_abandoned_branch() {
+        use super::IupacNucleotide::{A, C, G, T};
+
+        let mut state = Editor::default();
+        state.insert_all("AC".to_string()).unwrap();
+        state.insert_all("G".to_string()).unwrap();
+        state.undo().unwrap();
+        state.insert_all("T".to_string()).unwrap();
+        assert_eq!(state.sequence, [A, C, T]);
+
+        let abandoned = state
+            .history
+            .branches()
+            .into_iter()
+            .find(|branch| !branch.is_current)
+            .unwrap();
+        state.jump_to_history_node(abandoned.tip).unwrap();
+        assert_eq!(state.sequence, [A, C, G]);
+        assert_eq!(state.cursor_pos, 3);
+
+        state.undo().unwrap();
+        state.redo().unwrap();
+        assert_eq!(state.sequence, [A, C, G]);
+
+        state.jump_to_history_node(0).unwrap();
+        assert!(state.sequence.is_empty());
+        assert_eq!(
+            state.jump_to_history_node(42),
+            Err(EditorError::UnknownHistoryNode { id: 42 })
+        );
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        use super::CursorMovement;
+        use super::IupacNucleotide::{A, C, G, T};
+
+        let mut state = Editor::default();
+        state.insert_all("ACGT".to_string()).unwrap();
+        let checkpoint = state.create_checkpoint("as ordered".to_string());
+
+        state
+            .move_selection(SelectionMovement::Set { start: 1, end: 2 })
+            .unwrap();
+        state.insert('G').unwrap();
+        state.move_cursor(CursorMovement::End).unwrap();
+        state.insert_all("AA".to_string()).unwrap();
+        assert_eq!(state.sequence, [A, G, G, T, A, A]);
+
+        let hunks = state.diff_checkpoint(checkpoint.id).unwrap();
+        assert_eq!((hunks[0].old_start, hunks[0].new_start), (1, 1));
+        let last = hunks.last().unwrap();
+        assert_eq!((last.old_end, last.new_end), (4, 6));
+
+        state.restore_checkpoint(checkpoint.id).unwrap();
+        assert_eq!(state.sequence, [A, C, G, T]);
+        assert_eq!(state.cursor_pos, 4);
+
+        state.undo().unwrap();
+        assert_eq!(state.sequence, [A, G, G, T, A, A]);
+        assert_eq!(
+            state.restore_checkpoint(42),
+            Err(EditorError::UnknownCheckpoint { id: 42 })
+        );
+    }
+
+    #[test]
+    fn test_checkpoints_restore_case() {
+        let mut state = Editor::default();
+        state.insert_all("ACGT".to_string()).unwrap();
+        let checkpoint = state.create_checkpoint("uppercase".to_string());
+
+        state
+            .move_selection(SelectionMovement::Set { start: 1, end: 3 })
+            .unwrap();
+        state.insert_all("cg".to_string()).unwrap();
+        assert_eq!(state.sequence.letters(0..4), "AcgT");
+
+        let hunks = state.diff_checkpoint(checkpoint.id).unwrap();
+        assert_eq!(
+            hunks
+                .iter()
+                .map(|hunk| (hunk.old_start, hunk.old_end))
+                .collect::<Vec<_>>(),
+            [(1, 3)]
+        );
+
+        state.restore_checkpoint(checkpoint.id).unwrap();
+        assert_eq!(state.sequence.letters(0..4), "ACGT");
+        state.undo().unwrap();
+        assert_eq!(state.sequence.letters(0..4), "AcgT");
+    }
+
+    #[test]
+    fn test_cut_and_paste_is_one_step() {
+        use super::CursorMovement;
+        use super::IupacNucleotide::{A, C, G, T};
+
+        let mut state = Editor::default();
+        state.insert_all("AACCGGTT".to_string()).unwrap();
+        state
+            .move_selection(SelectionMovement::Set { start: 2, end: 4 })
+            .unwrap();
+        assert_eq!(
+            state.get_selected_strand(super::Strand::Reverse).unwrap().1,
+            "GG".to_string()
+        );
+
+        let cut_node = state.cut().ok();
+        assert_eq!(state.sequence, [A, A, G, G, T, T]);
+        state.move_cursor(CursorMovement::End).unwrap();
+        state.paste(&[C, C], &CaseMask::default(), cut_node);
+        assert_eq!(state.sequence, [A, A, G, G, T, T, C, C]);
+
+        state.undo().unwrap();
+        assert_eq!(state.sequence, [A, A, C, C, G, G, T, T]);
+
+        // Without the cut node the paste is a step of its own
+        state.move_cursor(CursorMovement::Start).unwrap();
+        state.paste(&[T], &CaseMask::default(), None);
+        state.undo().unwrap();
+        assert_eq!(state.sequence, [A, A, C, C, G, G, T, T]);
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        use super::CursorMovement;
+        use crate::search::Motif;
+        use plasmid::traits::ToLetter;
+
+        fn assert_codons_match(state: &Editor) {
+            let letters = state
+                .sequence
+                .iter()
+                .map(|nuc| nuc.to_letter())
+                .collect::<Vec<_>>();
+            let expected = letters.chunks(3).collect::<Vec<_>>();
+            let actual = state
+                .codons
+                .iter()
+                .map(|codon| codon.nucleotides.as_slice())
+                .collect::<Vec<_>>();
+            assert_eq!(actual, expected);
+        }
+
+        // Small linear congruential generator, so that the edits are reproducible
+        let mut seed = 42u32;
+        let mut random = |max: usize| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 16) as usize % max.max(1)
+        };
+
+        let mut state = Editor::default();
+        state.insert_all("ATGGCCAAGCTTGGATCC".to_string()).unwrap();
+        state.update();
+        assert_codons_match(&state);
+
+        let inserts = ["A", "GC", "TTA", "GGATCC", "ACGTA"];
+        for step in 0..300 {
+            for _ in 0..=random(2) {
+                let len = state.sequence.len();
+                match random(5) {
+                    0 | 1 => {
+                        state
+                            .move_cursor(CursorMovement::To(random(len + 1)))
+                            .unwrap();
+                        state
+                            .insert_all(inserts[random(inserts.len())].to_string())
+                            .unwrap();
+                    }
+                    2 => {
+                        let start = random(len + 1);
+                        let end = (start + random(7)).min(len);
+                        state
+                            .move_selection(SelectionMovement::Set { start, end })
+                            .unwrap();
+                        // Random edits may have nothing to delete or undo
+                        let _ = state.delete_next();
+                    }
+                    3 => {
+                        let _ = state.undo();
+                    }
+                    _ => {
+                        let motif = Motif::parse("GGATCC").unwrap();
+                        state
+                            .replace_all(&motif, &"AAGCTT"[..random(7)], false)
+                            .unwrap();
+                    }
+                }
+            }
+            state.update();
+            assert_codons_match(&state);
+            if step % 50 == 0 {
+                let _ = state.redo();
+            }
+        }
+    }
+
+    #[test]
+    fn test_sequence_items_window() {
+        use crate::shared::{PackedSequenceItems, SequenceItems};
+
+        let mut state = Editor::default();
+        state.insert_all("ATGGCCAAGCTTG".to_string()).unwrap();
+        state.update();
+
+        let items = |start, end| match state.sequence_items(start, end, false) {
+            (_, SequenceItems::Items(items)) => items,
+            (_, SequenceItems::Packed(_)) => unreachable!(),
+        };
+        let window = items(4, 8);
+        assert_eq!(
+            window
+                .iter()
+                .map(|item| item.start_index)
+                .collect::<Vec<_>>(),
+            [3, 6]
+        );
+        assert_eq!(window[0].codon, ['G', 'C', 'C']);
+        assert_eq!(items(0, usize::MAX).len(), 5);
+        assert_eq!(items(12, 100).len(), 1);
+        assert!(items(100, 200).is_empty());
+
+        match state.sequence_items(4, 14, true) {
+            (3, SequenceItems::Packed(packed)) => assert_eq!(
+                packed,
+                PackedSequenceItems {
+                    start_index: 3,
+                    codons: "GCCAAGCTTG".to_string(),
+                    anticodons: "CGGTTCGAAC".to_string(),
+                    peptides: "AKL ".to_string(),
+                }
+            ),
+            _ => panic!("expected packed items"),
+        }
+    }
+
+    #[test]
+    fn test_take_changes() {
+        use crate::shared::{ChangeKind, SequenceChange};
+
+        let mut state = Editor::default();
+        state.insert_all("ACGT".to_string()).unwrap();
+        state
+            .move_selection(SelectionMovement::Set { start: 1, end: 3 })
+            .unwrap();
+        state.insert('T').unwrap();
+        assert_eq!(
+            state.take_changes(),
+            [
+                SequenceChange {
+                    kind: ChangeKind::Inserted,
+                    start: 0,
+                    end: 4
+                },
+                SequenceChange {
+                    kind: ChangeKind::Deleted,
+                    start: 1,
+                    end: 3
+                },
+                SequenceChange {
+                    kind: ChangeKind::Inserted,
+                    start: 1,
+                    end: 2
+                },
+            ]
+        );
+        assert!(state.take_changes().is_empty());
+
+        state.undo().unwrap();
+        assert_eq!(state.take_changes().len(), 2);
+    }
+
+    #[test]
+    fn test_lowercase_is_preserved() {
+        use super::{CursorMovement, Strand};
+        use crate::search::Motif;
+
+        let mut state = Editor::default();
+        state.insert_all("ACgtaC".to_string()).unwrap();
+        state.insert('t').unwrap();
+        assert_eq!(state.sequence.letters(0..7), "ACgtaCt");
+
+        state
+            .move_selection(SelectionMovement::Set { start: 1, end: 4 })
+            .unwrap();
+        assert_eq!(state.get_selected_sequence(), "Cgt");
+        assert_eq!(state.get_selected_strand(Strand::Reverse).unwrap().1, "acG");
+
+        state.delete().unwrap();
+        assert_eq!(state.sequence.letters(0..4), "AaCt");
+        state.undo().unwrap();
+        assert_eq!(state.sequence.letters(0..7), "ACgtaCt");
+
+        state.move_cursor(CursorMovement::Start).unwrap();
+        let motif = Motif::parse("TAC").unwrap();
+        state.replace_all(&motif, "Ggg", false).unwrap();
+        assert_eq!(state.sequence.letters(0..7), "ACgGggt");
+        state.undo().unwrap();
+        assert_eq!(state.sequence.letters(0..7), "ACgtaCt");
     }
 }
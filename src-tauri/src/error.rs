@@ -0,0 +1,121 @@
+use std::{fmt, io};
+
+use crate::{documents::DocumentId, history::NodeId};
+
+/// Why a command couldn't be carried out.
+///
+/// Serialized with the variant name as `kind`, e.g.
+/// `{ "kind": "InvalidCharacter", "character": "X", "position": 4 }`.
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind")]
+pub enum EditorError {
+    /// A character which isn't an IUPAC nucleotide letter, at a character
+    /// position of the input.
+    InvalidCharacter {
+        character: char,
+        position: usize,
+    },
+    /// A typed letter which isn't an IUPAC nucleotide letter. Unlike with
+    /// [`EditorError::InvalidCharacter`], there is no input to have a position in.
+    InvalidLetter {
+        character: char,
+    },
+    /// A sequence position beyond the end of the sequence.
+    IndexOutOfRange {
+        index: usize,
+        len: usize,
+    },
+    /// The command needs a selection, but nothing is selected.
+    EmptySelection,
+    /// Deleting at the start or end of the sequence without a selection.
+    NothingToDelete,
+    NothingToUndo,
+    NothingToRedo,
+    /// A search query which couldn't be parsed.
+    InvalidQuery {
+        query: String,
+    },
+    UnknownCheckpoint {
+        id: usize,
+    },
+    UnknownHistoryNode {
+        id: NodeId,
+    },
+    /// A command for a document which isn't open, or was already closed.
+    UnknownDocument {
+        id: DocumentId,
+    },
+    /// The system clipboard couldn't be accessed.
+    Clipboard {
+        message: String,
+    },
+    /// A file couldn't be read or written.
+    Io {
+        message: String,
+    },
+    /// A project file which isn't valid JSON or misses fields.
+    InvalidProjectFile {
+        message: String,
+    },
+    /// A project file written by a newer version of the app.
+    UnsupportedVersion {
+        version: u32,
+    },
+}
+
+impl fmt::Display for EditorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditorError::InvalidCharacter {
+                character,
+                position,
+            } => write!(
+                f,
+                "'{}' at position {} is not a nucleotide",
+                character, position
+            ),
+            EditorError::InvalidLetter { character } => {
+                write!(f, "'{}' is not a nucleotide", character)
+            }
+            EditorError::IndexOutOfRange { index, len } => write!(
+                f,
+                "index {} is out of range for a sequence of length {}",
+                index, len
+            ),
+            EditorError::EmptySelection => write!(f, "nothing is selected"),
+            EditorError::NothingToDelete => write!(f, "nothing to delete"),
+            EditorError::NothingToUndo => write!(f, "nothing to undo"),
+            EditorError::NothingToRedo => write!(f, "nothing to redo"),
+            EditorError::InvalidQuery { query } => write!(f, "invalid query '{}'", query),
+            EditorError::UnknownCheckpoint { id } => write!(f, "no checkpoint with id {}", id),
+            EditorError::UnknownHistoryNode { id } => write!(f, "no history node with id {}", id),
+            EditorError::UnknownDocument { id } => write!(f, "document {} is not open", id),
+            EditorError::Clipboard { message } => write!(f, "clipboard error: {}", message),
+            EditorError::Io { message } => write!(f, "file error: {}", message),
+            EditorError::InvalidProjectFile { message } => {
+                write!(f, "invalid project file: {}", message)
+            }
+            EditorError::UnsupportedVersion { version } => {
+                write!(f, "unsupported project format version {}", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EditorError {}
+
+impl From<io::Error> for EditorError {
+    fn from(error: io::Error) -> Self {
+        EditorError::Io {
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for EditorError {
+    fn from(error: serde_json::Error) -> Self {
+        EditorError::InvalidProjectFile {
+            message: error.to_string(),
+        }
+    }
+}
@@ -1,198 +1,1125 @@
-use std::collections::VecDeque;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use plasmid::{traits::ToLetter, uni::IupacNucleotide};
+use plasmid::traits::ToLetter;
 
-use crate::editor::Selection;
+use crate::{
+    case_mask::CaseMask,
+    editor::Selection,
+    packed::PackedNucleotides,
+    sequence::Sequence,
+    shared::{HistoryBranchData, HistoryNodeData, HistoryStats},
+};
 
-#[derive(Debug, Default, Clone, PartialEq, Eq)]
-pub struct EditorSnapshot {
-    pub cursor_pos: usize,
-    pub selection: Option<Selection>,
-    pub sequence: Vec<IupacNucleotide>,
+/// A single reversible change to the sequence.
+///
+/// Positions refer to the sequence as it was right before the operation was applied.
+/// The nucleotides are kept packed like the sequence itself, but saved as letters.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum EditOperation {
+    Insert {
+        position: usize,
+        #[serde(with = "letters")]
+        nucleotides: PackedNucleotides,
+        /// Which of the nucleotides are lowercase, relative to `position`.
+        #[serde(default, skip_serializing_if = "CaseMask::is_empty")]
+        lowercase: CaseMask,
+    },
+    Delete {
+        position: usize,
+        #[serde(with = "letters")]
+        nucleotides: PackedNucleotides,
+        /// Which of the nucleotides are lowercase, relative to `position`.
+        #[serde(default, skip_serializing_if = "CaseMask::is_empty")]
+        lowercase: CaseMask,
+    },
 }
 
-impl std::fmt::Display for EditorSnapshot {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "EditorSnapshot(cursor_pos: {}, selection: {:?}, sequence: {:?})",
-            self.cursor_pos,
-            self.selection,
-            self.sequence
+impl EditOperation {
+    pub fn inverted(&self) -> Self {
+        match self {
+            EditOperation::Insert {
+                position,
+                nucleotides,
+                lowercase,
+            } => EditOperation::Delete {
+                position: *position,
+                nucleotides: nucleotides.clone(),
+                lowercase: lowercase.clone(),
+            },
+            EditOperation::Delete {
+                position,
+                nucleotides,
+                lowercase,
+            } => EditOperation::Insert {
+                position: *position,
+                nucleotides: nucleotides.clone(),
+                lowercase: lowercase.clone(),
+            },
+        }
+    }
+}
+
+/// One undo step: the operations of an edit in the order they were applied,
+/// together with the cursor and selection before and after the edit.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub operations: Vec<EditOperation>,
+    pub cursor_before: usize,
+    pub selection_before: Option<Selection>,
+    pub cursor_after: usize,
+    pub selection_after: Option<Selection>,
+}
+
+impl HistoryEntry {
+    fn new(cursor_pos: usize, selection: Option<Selection>) -> Self {
+        Self {
+            operations: Vec::new(),
+            cursor_before: cursor_pos,
+            selection_before: selection.clone(),
+            cursor_after: cursor_pos,
+            selection_after: selection,
+        }
+    }
+
+    /// Merges a typing step that directly follows this entry into it.
+    ///
+    /// Returns `false` if the steps aren't consecutive single letter insertions
+    /// or backspaces.
+    fn coalesce(&mut self, next: &HistoryEntry) -> bool {
+        if self.cursor_after != next.cursor_before
+            || self.selection_after.is_some()
+            || next.selection_before.is_some()
+        {
+            return false;
+        }
+
+        match (self.operations.as_mut_slice(), next.operations.as_slice()) {
+            (
+                [EditOperation::Insert {
+                    position,
+                    nucleotides,
+                    lowercase,
+                }],
+                [EditOperation::Insert {
+                    position: next_position,
+                    nucleotides: next_nucleotides,
+                    lowercase: next_lowercase,
+                }],
+            ) if next_nucleotides.len() == 1 && *next_position == *position + nucleotides.len() => {
+                lowercase.append(nucleotides.len(), next_lowercase);
+                *nucleotides = nucleotides.concat(next_nucleotides);
+            }
+            (
+                [EditOperation::Delete {
+                    position,
+                    nucleotides,
+                    lowercase,
+                }],
+                [EditOperation::Delete {
+                    position: next_position,
+                    nucleotides: next_nucleotides,
+                    lowercase: next_lowercase,
+                }],
+            ) if next_nucleotides.len() == 1 && *next_position + 1 == *position => {
+                *position = *next_position;
+                lowercase.insert(0, 1, next_lowercase);
+                *nucleotides = next_nucleotides.concat(nucleotides);
+            }
+            _ => return false,
+        }
+
+        self.cursor_after = next.cursor_after;
+        self.selection_after = next.selection_after.clone();
+        true
+    }
+
+    /// Number of inserted and deleted nucleotides.
+    pub fn changed_nucleotides(&self) -> (usize, usize) {
+        self.operations
+            .iter()
+            .fold((0, 0), |(inserted, deleted), operation| match operation {
+                EditOperation::Insert { nucleotides, .. } => {
+                    (inserted + nucleotides.len(), deleted)
+                }
+                EditOperation::Delete { nucleotides, .. } => {
+                    (inserted, deleted + nucleotides.len())
+                }
+            })
+    }
+
+    /// Length of the sequence after applying the entry to a sequence of `len`
+    /// nucleotides, or `None` if an operation, cursor or selection wouldn't fit.
+    fn len_after(&self, len: usize) -> Option<usize> {
+        let fits = |cursor: usize, selection: &Option<Selection>, len: usize| {
+            cursor <= len
+                && selection.as_ref().map_or(true, |selection| {
+                    selection.start <= selection.end && selection.end <= len
+                })
+        };
+        if !fits(self.cursor_before, &self.selection_before, len) {
+            return None;
+        }
+        let len = self
+            .operations
+            .iter()
+            .try_fold(len, |len, operation| match operation {
+                EditOperation::Insert {
+                    position,
+                    nucleotides,
+                    ..
+                } => (*position <= len).then(|| len + nucleotides.len()),
+                EditOperation::Delete {
+                    position,
+                    nucleotides,
+                    ..
+                } => position
+                    .checked_add(nucleotides.len())
+                    .filter(|&end| end <= len)
+                    .map(|_| len - nucleotides.len()),
+            })?;
+        fits(self.cursor_after, &self.selection_after, len).then(|| len)
+    }
+
+    /// Length of the sequence before the entry, given the length after it.
+    fn len_before(&self, len: usize) -> Option<usize> {
+        let inverted = HistoryEntry {
+            operations: self
+                .operations
                 .iter()
-                .map(|n| n.to_letter())
-                .collect::<String>()
-        )
+                .rev()
+                .map(EditOperation::inverted)
+                .collect(),
+            cursor_before: self.cursor_after,
+            selection_before: self.selection_after.clone(),
+            cursor_after: self.cursor_before,
+            selection_after: self.selection_before.clone(),
+        };
+        inverted.len_after(len)
+    }
+
+    /// Approximate heap and inline memory used by the entry.
+    pub fn size_in_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self
+                .operations
+                .iter()
+                .map(|operation| match operation {
+                    EditOperation::Insert {
+                        nucleotides,
+                        lowercase,
+                        ..
+                    }
+                    | EditOperation::Delete {
+                        nucleotides,
+                        lowercase,
+                        ..
+                    } => {
+                        std::mem::size_of::<EditOperation>()
+                            + nucleotides.heap_size()
+                            + lowercase.heap_size()
+                    }
+                })
+                .sum::<usize>()
     }
 }
 
-#[derive(Debug, Default)]
-pub struct EditorSnapshotHistory {
-    pub snapshots: VecDeque<EditorSnapshot>,
-    index: usize,
+/// Typing pauses longer than this start a new undo step.
+const COALESCE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryLimits {
+    pub max_entries: usize,
+    pub max_bytes: usize,
+}
+
+impl Default for HistoryLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 1000,
+            max_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+pub type NodeId = usize;
+
+/// A state in the undo tree. Every node except the root holds the entry
+/// which leads from its parent to it.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+struct HistoryNode {
+    parent: Option<NodeId>,
+    entry: Option<HistoryEntry>,
+    children: Vec<NodeId>,
+    /// The child which redo moves to, i.e. the most recently visited one.
+    redo_child: Option<NodeId>,
+    created_at: SystemTime,
+}
+
+impl HistoryNode {
+    fn new(parent: Option<NodeId>, entry: Option<HistoryEntry>) -> Self {
+        Self {
+            parent,
+            entry,
+            children: Vec::new(),
+            redo_child: None,
+            created_at: SystemTime::now(),
+        }
+    }
+}
+
+/// A step on the way from one node of the undo tree to another.
+pub enum HistoryStep {
+    Undo(HistoryEntry),
+    Redo(HistoryEntry),
 }
 
-impl EditorSnapshotHistory {
+/// Undo tree of an editor.
+///
+/// New edits never discard redo states; they start a new branch instead.
+/// Node ids are assigned in creation order and stay valid until the node is evicted.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct EditorHistory {
+    nodes: BTreeMap<NodeId, HistoryNode>,
+    root: NodeId,
+    current: NodeId,
+    next_id: NodeId,
+    #[serde(skip)]
+    pending: Option<HistoryEntry>,
+    #[serde(skip)]
+    limits: HistoryLimits,
+    /// Recomputed on load, so that a saved history can't misreport its size.
+    #[serde(skip)]
+    size_in_bytes: usize,
+    /// When the last entry was committed, if further typing may be merged into it.
+    #[serde(skip)]
+    coalesce_since: Option<Instant>,
+}
+
+/// An undo tree as stored together with its document.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct SavedHistory {
+    /// Checksum of the sequence at the time of saving, so that a history
+    /// doesn't get applied to a document that was changed elsewhere.
+    checksum: u32,
+    history: EditorHistory,
+}
+
+impl Default for EditorHistory {
+    fn default() -> Self {
+        Self {
+            nodes: BTreeMap::from([(0, HistoryNode::new(None, None))]),
+            root: 0,
+            current: 0,
+            next_id: 1,
+            pending: None,
+            limits: HistoryLimits::default(),
+            size_in_bytes: 0,
+            coalesce_since: None,
+        }
+    }
+}
+
+impl EditorHistory {
     pub fn clear(&mut self) {
-        self.snapshots.clear();
-        self.index = 0;
+        *self = Self {
+            limits: self.limits,
+            ..Self::default()
+        };
+    }
+
+    pub fn save(&self, sequence: &Sequence) -> SavedHistory {
+        SavedHistory {
+            checksum: checksum(sequence),
+            history: self.clone(),
+        }
     }
 
-    pub fn push(&mut self, snapshot: EditorSnapshot) {
-        // Truncate snapshots. Not sure whether this is a good idea or not..
-        if self.snapshots.len() >= 2 && self.index < self.snapshots.len().saturating_sub(1) {
-            self.snapshots.truncate(self.index + 1);
+    /// Replaces the undo tree with a saved one, keeping the current limits.
+    ///
+    /// Returns `false` if the history doesn't belong to the sequence or is malformed.
+    pub fn load(&mut self, saved: SavedHistory, sequence: &Sequence) -> bool {
+        let history = saved.history;
+        if saved.checksum != checksum(sequence) || !history.is_well_formed(sequence.len()) {
+            return false;
         }
 
-        // Disallow pushing the same snapshot twice
-        if let Some(last_snapshot) = self.snapshots.iter().last() {
-            if &snapshot == last_snapshot {
-                return;
+        let size_in_bytes = history
+            .nodes
+            .values()
+            .filter_map(|node| node.entry.as_ref())
+            .map(HistoryEntry::size_in_bytes)
+            .sum();
+        *self = Self {
+            limits: self.limits,
+            size_in_bytes,
+            ..history
+        };
+        self.evict();
+        true
+    }
+
+    pub fn current(&self) -> NodeId {
+        self.current
+    }
+
+    /// Whether a deserialized tree can be used without panicking: the nodes form a
+    /// single tree below the root, every link points both ways, and every entry
+    /// fits the sequence lengths along the way, given `len` at the current node.
+    fn is_well_formed(&self, len: usize) -> bool {
+        let root = match self.nodes.get(&self.root) {
+            Some(root) => root,
+            None => return false,
+        };
+        if root.parent.is_some()
+            || root.entry.is_some()
+            || self.nodes.keys().any(|&id| id >= self.next_id)
+        {
+            return false;
+        }
+        let links_are_valid = self.nodes.iter().all(|(id, node)| {
+            let parent_is_valid = match node.parent {
+                Some(parent) => {
+                    node.entry.is_some()
+                        && self
+                            .nodes
+                            .get(&parent)
+                            .map_or(false, |parent| parent.children.contains(id))
+                }
+                None => *id == self.root,
+            };
+            parent_is_valid
+                && node.children.iter().all(|child| {
+                    self.nodes
+                        .get(child)
+                        .map_or(false, |child| child.parent == Some(*id))
+                })
+                && node
+                    .redo_child
+                    .map_or(true, |child| node.children.contains(&child))
+        });
+        if !links_are_valid || !self.nodes.contains_key(&self.current) {
+            return false;
+        }
+
+        // Every node must be reachable from the root exactly once, so there are no
+        // cycles. Walking up from the current node gives the length at the root.
+        let mut root_len = Some(len);
+        let mut id = self.current;
+        let mut steps = 0;
+        while let Some(parent) = self.nodes[&id].parent {
+            steps += 1;
+            if steps > self.nodes.len() {
+                return false;
+            }
+            root_len = root_len.and_then(|len| self.nodes[&id].entry.as_ref()?.len_before(len));
+            id = parent;
+        }
+        let root_len = match root_len {
+            Some(len) if id == self.root => len,
+            _ => return false,
+        };
+
+        let mut visited = HashSet::new();
+        let mut stack = vec![(self.root, root_len)];
+        while let Some((id, len)) = stack.pop() {
+            if !visited.insert(id) {
+                return false;
+            }
+            for child in &self.nodes[&id].children {
+                match self.nodes[child]
+                    .entry
+                    .as_ref()
+                    .and_then(|entry| entry.len_after(len))
+                {
+                    Some(child_len) => stack.push((*child, child_len)),
+                    None => return false,
+                }
             }
         }
+        visited.len() == self.nodes.len()
+    }
 
-        self.snapshots.push_back(snapshot);
-        self.index = self.snapshots.len() - 1;
+    /// Prevents the next edit from being merged into the last entry.
+    pub fn seal(&mut self) {
+        self.coalesce_since = None;
     }
 
-    pub fn get_undo_snapshot(&mut self) -> Option<EditorSnapshot> {
-        if self.index > 0 {
-            self.index -= 1;
-            Some(self.snapshots[self.index].clone())
-        } else {
-            None
+    pub fn set_limits(&mut self, limits: HistoryLimits) {
+        self.limits = limits;
+        self.evict();
+    }
+
+    pub fn stats(&self) -> HistoryStats {
+        HistoryStats {
+            entry_count: self.nodes.len() - 1,
+            undo_count: self.ancestors(self.current).len() - 1,
+            redo_count: self.redo_chain().len(),
+            size_in_bytes: self.size_in_bytes,
+            max_entries: self.limits.max_entries,
+            max_bytes: self.limits.max_bytes,
         }
     }
 
-    pub fn get_redo_snapshot(&mut self) -> Option<EditorSnapshot> {
-        if self.index < self.snapshots.len() - 1 {
-            self.index += 1;
-            let result = Some(self.snapshots[self.index].clone());
-            result
-        } else {
-            None
+    /// Lists every node of the undo tree, oldest first.
+    pub fn nodes(&self) -> Vec<HistoryNodeData> {
+        self.nodes
+            .iter()
+            .map(|(&id, node)| {
+                let (inserted, deleted) = node
+                    .entry
+                    .as_ref()
+                    .map_or((0, 0), HistoryEntry::changed_nucleotides);
+                HistoryNodeData {
+                    id,
+                    parent: node.parent,
+                    children: node.children.clone(),
+                    is_current: id == self.current,
+                    inserted,
+                    deleted,
+                    created_at: timestamp(node.created_at),
+                }
+            })
+            .collect()
+    }
+
+    /// Lists the tips of all branches, oldest first.
+    pub fn branches(&self) -> Vec<HistoryBranchData> {
+        let current_path = self.ancestors(self.current);
+        self.nodes
+            .iter()
+            .filter(|(_, node)| node.children.is_empty())
+            .map(|(&tip, node)| {
+                let path = self.ancestors(tip);
+                HistoryBranchData {
+                    tip,
+                    fork: *path.iter().find(|id| current_path.contains(id)).unwrap(),
+                    length: path.len() - 1,
+                    is_current: path.contains(&self.current),
+                    created_at: timestamp(node.created_at),
+                }
+            })
+            .collect()
+    }
+
+    /// Starts recording an edit.
+    pub fn begin(&mut self, cursor_pos: usize, selection: Option<Selection>) {
+        self.pending = Some(HistoryEntry::new(cursor_pos, selection));
+    }
+
+    /// Records an operation of the current edit.
+    pub fn record(&mut self, operation: EditOperation) {
+        if let Some(pending) = &mut self.pending {
+            pending.operations.push(operation);
         }
     }
 
-    #[cfg(test)]
-    pub fn peek_undo_snapshot(&self) -> Option<&EditorSnapshot> {
-        if self.index > 0 {
-            Some(&self.snapshots[self.index - 1])
-        } else {
-            None
+    /// Finishes the current edit. Edits which didn't change the sequence are discarded.
+    pub fn commit(&mut self, cursor_pos: usize, selection: Option<Selection>) {
+        self.commit_entry(cursor_pos, selection, false);
+    }
+
+    /// Finishes the current edit, merging it into the last entry if both are
+    /// consecutive single letter insertions or backspaces typed without a pause.
+    pub fn commit_coalescing(&mut self, cursor_pos: usize, selection: Option<Selection>) {
+        self.commit_entry(cursor_pos, selection, true);
+    }
+
+    /// Finishes the current edit by appending it to the entry of `node`, so that
+    /// both are undone together. Falls back to a new entry unless `node` is the
+    /// current state and has no redo branches.
+    pub fn commit_appending(
+        &mut self,
+        node: NodeId,
+        cursor_pos: usize,
+        selection: Option<Selection>,
+    ) {
+        let current = self.nodes.get_mut(&self.current).unwrap();
+        if let (true, true, Some(last), Some(pending)) = (
+            self.current == node,
+            current.children.is_empty(),
+            &mut current.entry,
+            &mut self.pending,
+        ) {
+            let size_before = last.size_in_bytes();
+            last.operations.append(&mut pending.operations);
+            last.cursor_after = cursor_pos;
+            last.selection_after = selection;
+            self.size_in_bytes = self.size_in_bytes - size_before + last.size_in_bytes();
+            self.pending = None;
+            self.coalesce_since = None;
+            self.evict();
+            return;
         }
+        self.commit(cursor_pos, selection);
     }
 
-    #[cfg(test)]
-    pub fn peek_redo_snapshot(&self) -> Option<&EditorSnapshot> {
-        if self.index < self.snapshots.len() - 1 {
-            Some(&self.snapshots[self.index + 1])
-        } else {
-            None
+    fn commit_entry(&mut self, cursor_pos: usize, selection: Option<Selection>, coalesce: bool) {
+        let mut entry = match self.pending.take() {
+            Some(entry) if !entry.operations.is_empty() => entry,
+            _ => return,
+        };
+        entry.cursor_after = cursor_pos;
+        entry.selection_after = selection;
+
+        let can_coalesce = coalesce
+            && self
+                .coalesce_since
+                .map_or(false, |since| since.elapsed() < COALESCE_TIMEOUT);
+        self.coalesce_since = if coalesce { Some(Instant::now()) } else { None };
+
+        if can_coalesce {
+            let current = self.nodes.get_mut(&self.current).unwrap();
+            if let (true, Some(last)) = (current.children.is_empty(), &mut current.entry) {
+                let size_before = last.size_in_bytes();
+                if last.coalesce(&entry) {
+                    self.size_in_bytes = self.size_in_bytes - size_before + last.size_in_bytes();
+                    self.evict();
+                    return;
+                }
+            }
+        }
+
+        // A new edit starts a new branch, the previous redo states are kept
+        let id = self.next_id;
+        self.next_id += 1;
+        self.size_in_bytes += entry.size_in_bytes();
+        self.nodes
+            .insert(id, HistoryNode::new(Some(self.current), Some(entry)));
+        let parent = self.nodes.get_mut(&self.current).unwrap();
+        parent.children.push(id);
+        parent.redo_child = Some(id);
+        self.current = id;
+        self.evict();
+    }
+
+    pub fn get_undo_entry(&mut self) -> Option<HistoryEntry> {
+        let node = &self.nodes[&self.current];
+        let (parent, entry) = (node.parent?, node.entry.clone()?);
+        self.nodes.get_mut(&parent).unwrap().redo_child = Some(self.current);
+        self.current = parent;
+        Some(entry)
+    }
+
+    pub fn get_redo_entry(&mut self) -> Option<HistoryEntry> {
+        let child = self.nodes[&self.current].redo_child?;
+        self.current = child;
+        self.nodes[&child].entry.clone()
+    }
+
+    /// Moves to any node of the tree. Returns the steps which need to be applied,
+    /// or `None` if the node doesn't exist.
+    pub fn jump_to(&mut self, target: NodeId) -> Option<Vec<HistoryStep>> {
+        if !self.nodes.contains_key(&target) {
+            return None;
+        }
+
+        let target_path = self.ancestors(target);
+        let mut steps = Vec::new();
+        while !target_path.contains(&self.current) {
+            steps.push(HistoryStep::Undo(self.get_undo_entry()?));
+        }
+        let fork = target_path.iter().position(|&id| id == self.current)?;
+        for &id in target_path[..fork].iter().rev() {
+            self.nodes.get_mut(&self.current).unwrap().redo_child = Some(id);
+            steps.push(HistoryStep::Redo(self.get_redo_entry()?));
         }
+        Some(steps)
+    }
+
+    /// The node itself followed by its ancestors up to the root.
+    fn ancestors(&self, id: NodeId) -> Vec<NodeId> {
+        let mut path = vec![id];
+        while let Some(parent) = self.nodes[path.last().unwrap()].parent {
+            path.push(parent);
+        }
+        path
+    }
+
+    /// The nodes redo would move through, starting after the current node.
+    fn redo_chain(&self) -> Vec<NodeId> {
+        let mut chain = Vec::new();
+        let mut id = self.current;
+        while let Some(child) = self.nodes[&id].redo_child {
+            chain.push(child);
+            id = child;
+        }
+        chain
+    }
+
+    fn exceeds_limits(&self) -> bool {
+        self.nodes.len() - 1 > self.limits.max_entries || self.size_in_bytes > self.limits.max_bytes
+    }
+
+    /// Drops entries until the history fits into its limits.
+    ///
+    /// Leaves which are neither on the path to the current node nor on its redo
+    /// chain go first, oldest first. After that the oldest undo entries are
+    /// dropped by moving the root towards the current node, and finally the
+    /// newest redo entries.
+    fn evict(&mut self) {
+        if !self.exceeds_limits() {
+            return;
+        }
+
+        // Ordered from the current node to the root
+        let mut current_path = self.ancestors(self.current);
+        let mut redo_chain = self.redo_chain();
+        let kept = current_path
+            .iter()
+            .chain(&redo_chain)
+            .cloned()
+            .collect::<HashSet<_>>();
+        // Ids are assigned in creation order, so the smallest id is the oldest leaf
+        let mut leaves = self
+            .nodes
+            .iter()
+            .filter(|(id, node)| node.children.is_empty() && !kept.contains(id))
+            .map(|(&id, _)| id)
+            .collect::<BTreeSet<_>>();
+
+        while self.exceeds_limits() {
+            if let Some(leaf) = leaves.iter().next().cloned() {
+                leaves.remove(&leaf);
+                let parent = self.remove_leaf(leaf);
+                if self.nodes[&parent].children.is_empty() && !kept.contains(&parent) {
+                    leaves.insert(parent);
+                }
+            } else if self.current != self.root {
+                current_path.pop();
+                let new_root = *current_path.last().unwrap();
+                self.nodes.remove(&self.root);
+                let node = self.nodes.get_mut(&new_root).unwrap();
+                node.parent = None;
+                self.size_in_bytes -= node.entry.take().map_or(0, |e| e.size_in_bytes());
+                self.root = new_root;
+            } else if let Some(leaf) = redo_chain.pop() {
+                self.remove_leaf(leaf);
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Removes a node without children and returns its parent.
+    fn remove_leaf(&mut self, id: NodeId) -> NodeId {
+        let node = self.nodes.remove(&id).unwrap();
+        self.size_in_bytes -= node.entry.map_or(0, |e| e.size_in_bytes());
+        let parent_id = node.parent.unwrap();
+        let parent = self.nodes.get_mut(&parent_id).unwrap();
+        parent.children.retain(|&child| child != id);
+        if parent.redo_child == Some(id) {
+            parent.redo_child = parent.children.last().cloned();
+        }
+        parent_id
+    }
+
+    #[cfg(test)]
+    pub fn peek_undo_entry(&self) -> Option<&HistoryEntry> {
+        self.nodes[&self.current].entry.as_ref()
+    }
+
+    #[cfg(test)]
+    pub fn peek_redo_entry(&self) -> Option<&HistoryEntry> {
+        let child = self.nodes[&self.current].redo_child?;
+        self.nodes[&child].entry.as_ref()
     }
 }
 
+/// FNV-1a over the letters of a sequence.
+fn checksum(sequence: &Sequence) -> u32 {
+    sequence.iter().fold(0x811c9dc5, |hash, nucleotide| {
+        (hash ^ nucleotide.to_letter() as u32).wrapping_mul(0x01000193)
+    })
+}
+
+/// Stores nucleotides as a string of IUPAC letters.
+mod letters {
+    use plasmid::{
+        traits::{ToLetter, TryFromLetter},
+        uni::IupacNucleotide,
+    };
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use crate::packed::PackedNucleotides;
+
+    pub fn serialize<S: Serializer>(
+        nucleotides: &PackedNucleotides,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(
+            &nucleotides
+                .nucleotides(0..nucleotides.len())
+                .map(|n| n.to_letter())
+                .collect::<String>(),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<PackedNucleotides, D::Error> {
+        let nucleotides = String::deserialize(deserializer)?
+            .chars()
+            .map(|c| {
+                IupacNucleotide::try_from_letter(c)
+                    .map_err(|_| D::Error::custom(format!("invalid nucleotide '{}'", c)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PackedNucleotides::from_nucleotides(&nucleotides))
+    }
+}
+
+fn timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{EditorSnapshot, EditorSnapshotHistory};
+    use std::time::{Duration, Instant};
 
-    #[test]
-    fn test_undo() {
-        use plasmid::uni::IupacNucleotide::*;
+    use plasmid::{traits::TryFromLetter, uni::IupacNucleotide};
 
-        let mut history = EditorSnapshotHistory::default();
+    use super::{EditOperation, EditorHistory, HistoryEntry, HistoryLimits, NodeId, SavedHistory};
+    use crate::{case_mask::CaseMask, packed::PackedNucleotides};
 
-        history.push(EditorSnapshot::default());
-        assert_eq!(history.index, 0);
-        assert_eq!(history.peek_undo_snapshot(), None);
+    fn nucleotides(letters: &str) -> Vec<IupacNucleotide> {
+        letters
+            .chars()
+            .map(|c| IupacNucleotide::try_from_letter(c).unwrap())
+            .collect()
+    }
+
+    fn packed(letters: &str) -> PackedNucleotides {
+        PackedNucleotides::from_nucleotides(&nucleotides(letters))
+    }
 
-        history.push(EditorSnapshot {
-            cursor_pos: 3,
-            selection: None,
-            sequence: vec![A, T, G],
+    fn entry(history: &EditorHistory, id: NodeId) -> &HistoryEntry {
+        history.nodes[&id].entry.as_ref().unwrap()
+    }
+
+    fn insert(history: &mut EditorHistory, position: usize, letters: &str) {
+        let nucleotides = packed(letters);
+        history.begin(position, None);
+        history.record(EditOperation::Insert {
+            position,
+            nucleotides: nucleotides.clone(),
+            lowercase: CaseMask::default(),
         });
-        assert_eq!(history.index, 1);
-        assert_eq!(
-            history.peek_undo_snapshot(),
-            Some(&EditorSnapshot::default())
-        );
+        history.commit(position + nucleotides.len(), None);
+    }
 
-        history.get_undo_snapshot().unwrap();
-        assert_eq!(history.index, 0);
-        assert_eq!(history.get_undo_snapshot(), None);
+    fn type_letter(history: &mut EditorHistory, position: usize, letter: &str) {
+        history.begin(position, None);
+        history.record(EditOperation::Insert {
+            position,
+            nucleotides: packed(letter),
+            lowercase: CaseMask::default(),
+        });
+        history.commit_coalescing(position + 1, None);
+    }
+
+    fn backspace(history: &mut EditorHistory, position: usize, letter: &str) {
+        history.begin(position, None);
+        history.record(EditOperation::Delete {
+            position: position - 1,
+            nucleotides: packed(letter),
+            lowercase: CaseMask::default(),
+        });
+        history.commit_coalescing(position - 1, None);
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut history = EditorHistory::default();
+        assert_eq!(history.peek_undo_entry(), None);
+
+        insert(&mut history, 0, "ATG");
+        assert_eq!(history.stats().undo_count, 1);
+        assert_eq!(history.peek_undo_entry().unwrap().cursor_after, 3);
+
+        let entry = history.get_undo_entry().unwrap();
+        assert_eq!(entry.cursor_before, 0);
+        assert_eq!(history.stats().undo_count, 0);
+        assert_eq!(history.get_undo_entry(), None);
     }
 
     #[test]
     fn test_redo() {
-        use plasmid::uni::IupacNucleotide::*;
+        let mut history = EditorHistory::default();
 
-        let mut history = EditorSnapshotHistory::default();
+        insert(&mut history, 0, "ATG");
+        assert_eq!(history.stats().undo_count, 1);
+        assert_eq!(history.peek_redo_entry(), None);
 
-        history.push(EditorSnapshot::default());
-        history.push(EditorSnapshot {
-            cursor_pos: 3,
-            selection: None,
-            sequence: vec![A, T, G],
-        });
-        assert_eq!(history.index, 1);
+        history.get_undo_entry().unwrap();
+        assert_eq!(history.stats().undo_count, 0);
+        assert_eq!(history.peek_redo_entry().unwrap().cursor_after, 3);
 
-        history.get_undo_snapshot().unwrap();
-        assert_eq!(history.index, 0);
-        assert_eq!(
-            history.peek_redo_snapshot(),
-            Some(&EditorSnapshot {
-                cursor_pos: 3,
-                selection: None,
-                sequence: vec![A, T, G],
-            })
-        );
+        history.get_redo_entry().unwrap();
+        assert_eq!(history.stats().undo_count, 1);
+        assert_eq!(history.get_redo_entry(), None);
+    }
+
+    #[test]
+    fn test_branching() {
+        let mut history = EditorHistory::default();
+
+        insert(&mut history, 0, "ATGC");
+        insert(&mut history, 4, "TG");
+        history.get_undo_entry().unwrap();
+        insert(&mut history, 3, "TA");
+
+        assert_eq!(history.stats().entry_count, 3);
+        assert_eq!(history.stats().undo_count, 2);
+        assert_eq!(history.stats().redo_count, 0);
+        assert_eq!(entry(&history, 2).cursor_after, 6);
+        assert_eq!(entry(&history, 3).cursor_before, 3);
 
-        history.get_redo_snapshot().unwrap();
-        assert_eq!(history.index, 1);
+        let branches = history.branches();
+        assert_eq!(branches.len(), 2);
+        assert_eq!((branches[0].tip, branches[0].fork), (2, 1));
+        assert!(!branches[0].is_current);
+        assert_eq!((branches[1].tip, branches[1].fork), (3, 3));
+        assert!(branches[1].is_current);
+
+        // Undo goes back to the fork, redo follows the most recent branch
+        history.get_undo_entry().unwrap();
+        assert_eq!(history.get_redo_entry().unwrap().cursor_after, 5);
+    }
+
+    #[test]
+    fn test_jump_to() {
+        use super::HistoryStep;
+
+        let mut history = EditorHistory::default();
+
+        insert(&mut history, 0, "ATGC");
+        insert(&mut history, 4, "TG");
+        history.get_undo_entry().unwrap();
+        insert(&mut history, 3, "TA");
+
+        let steps = history.jump_to(2).unwrap();
+        assert!(matches!(
+            steps.as_slice(),
+            [HistoryStep::Undo(undone), HistoryStep::Redo(redone)]
+                if undone.cursor_after == 5 && redone.cursor_after == 6
+        ));
+        assert_eq!(history.current, 2);
+        assert_eq!(history.get_undo_entry().unwrap().cursor_after, 6);
+        assert_eq!(history.get_redo_entry().unwrap().cursor_after, 6);
+
+        assert!(history.jump_to(42).is_none());
     }
 
     #[test]
-    fn test_truncation() {
-        use plasmid::uni::IupacNucleotide::*;
+    fn test_empty_edit_is_discarded() {
+        let mut history = EditorHistory::default();
 
-        let mut history = EditorSnapshotHistory::default();
+        history.begin(0, None);
+        history.commit(0, None);
+        assert_eq!(history.stats().entry_count, 0);
+    }
 
-        history.push(EditorSnapshot::default());
-        history.push(EditorSnapshot {
-            cursor_pos: 4,
-            selection: None,
-            sequence: vec![A, T, G, C],
+    #[test]
+    fn test_entry_limit() {
+        let mut history = EditorHistory::default();
+        history.set_limits(HistoryLimits {
+            max_entries: 2,
+            ..HistoryLimits::default()
         });
-        history.push(EditorSnapshot {
-            cursor_pos: 6,
-            selection: None,
-            sequence: vec![A, T, G, C, T, G],
+
+        insert(&mut history, 0, "A");
+        insert(&mut history, 1, "T");
+        insert(&mut history, 2, "G");
+
+        assert_eq!(history.stats().entry_count, 2);
+        assert_eq!(entry(&history, 2).cursor_before, 1);
+        assert_eq!(history.stats().undo_count, 2);
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let mut history = EditorHistory::default();
+
+        insert(&mut history, 0, "ATGC");
+        insert(&mut history, 4, "ATGC");
+        let entry_size = entry(&history, 1).size_in_bytes();
+        assert_eq!(history.stats().size_in_bytes, 2 * entry_size);
+
+        history.get_undo_entry().unwrap();
+        history.set_limits(HistoryLimits {
+            max_bytes: entry_size,
+            ..HistoryLimits::default()
         });
-        history.get_undo_snapshot().unwrap();
-        history.push(EditorSnapshot {
-            cursor_pos: 6,
-            selection: None,
-            sequence: vec![A, T, G, T, A, G],
+
+        assert_eq!(history.stats().entry_count, 1);
+        assert_eq!(history.stats().size_in_bytes, entry_size);
+        assert_eq!(history.stats().undo_count, 0);
+        assert_eq!(history.stats().redo_count, 1);
+    }
+
+    #[test]
+    fn test_entry_size_counts_packed_nucleotides() {
+        let mut history = EditorHistory::default();
+        insert(&mut history, 0, "A");
+        insert(&mut history, 1, &"ACGT".repeat(100));
+        let overhead = entry(&history, 1).size_in_bytes() - 1;
+
+        // Four bases per byte
+        assert_eq!(entry(&history, 2).size_in_bytes(), overhead + 100);
+    }
+
+    #[test]
+    fn test_branches_below_current_are_evicted() {
+        let mut history = EditorHistory::default();
+
+        insert(&mut history, 0, "A");
+        insert(&mut history, 1, "T");
+        history.get_undo_entry().unwrap();
+        history.get_undo_entry().unwrap();
+        insert(&mut history, 0, "G");
+        history.get_undo_entry().unwrap();
+        history.set_limits(HistoryLimits {
+            max_entries: 1,
+            ..HistoryLimits::default()
         });
 
-        assert_eq!(history.snapshots.len(), 3);
-        assert_eq!(history.snapshots[0], EditorSnapshot::default());
+        // The older branch goes, the one redo would move to stays
+        assert_eq!(history.stats().entry_count, 1);
+        assert_eq!(history.stats().redo_count, 1);
+        assert_eq!(history.get_redo_entry().unwrap(), *entry(&history, 3));
+    }
+
+    #[test]
+    fn test_coalesce_typing() {
+        let mut history = EditorHistory::default();
+
+        type_letter(&mut history, 0, "A");
+        type_letter(&mut history, 1, "T");
+        type_letter(&mut history, 2, "G");
+        assert_eq!(history.stats().entry_count, 1);
         assert_eq!(
-            history.snapshots[1],
-            EditorSnapshot {
-                cursor_pos: 4,
-                selection: None,
-                sequence: vec![A, T, G, C],
-            }
+            entry(&history, 1).operations,
+            [EditOperation::Insert {
+                position: 0,
+                nucleotides: packed("ATG"),
+                lowercase: CaseMask::default(),
+            }]
         );
+        assert_eq!(entry(&history, 1).cursor_before, 0);
+        assert_eq!(entry(&history, 1).cursor_after, 3);
+
+        backspace(&mut history, 3, "G");
+        backspace(&mut history, 2, "T");
+        assert_eq!(history.stats().entry_count, 2);
         assert_eq!(
-            history.snapshots[2],
-            EditorSnapshot {
-                cursor_pos: 6,
-                selection: None,
-                sequence: vec![A, T, G, T, A, G],
-            }
+            entry(&history, 2).operations,
+            [EditOperation::Delete {
+                position: 1,
+                nucleotides: packed("TG"),
+                lowercase: CaseMask::default(),
+            }]
         );
     }
+
+    #[test]
+    fn test_coalesce_breaks() {
+        let mut history = EditorHistory::default();
+
+        // Cursor jump
+        type_letter(&mut history, 0, "A");
+        type_letter(&mut history, 0, "T");
+        assert_eq!(history.stats().entry_count, 2);
+
+        // Explicit break, e.g. after moving the cursor
+        history.seal();
+        type_letter(&mut history, 1, "G");
+        assert_eq!(history.stats().entry_count, 3);
+
+        // Pause
+        history.coalesce_since = Some(Instant::now() - Duration::from_secs(5));
+        type_letter(&mut history, 2, "C");
+        assert_eq!(history.stats().entry_count, 4);
+
+        // Regular edits are never merged
+        insert(&mut history, 3, "A");
+        type_letter(&mut history, 4, "A");
+        insert(&mut history, 5, "A");
+        assert_eq!(history.stats().entry_count, 7);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut history = EditorHistory::default();
+        insert(&mut history, 0, "ACGT");
+        insert(&mut history, 4, "TT");
+        history.get_undo_entry();
+        insert(&mut history, 4, "GG");
+        let sequence = nucleotides("ACGTGG").into_iter().collect();
+
+        let json = serde_json::to_string(&history.save(&sequence)).unwrap();
+        let saved = || serde_json::from_str::<SavedHistory>(&json).unwrap();
+
+        let mut loaded = EditorHistory::default();
+        assert!(!loaded.load(saved(), &nucleotides("ACGTTT").into_iter().collect()));
+        assert!(loaded.load(saved(), &sequence));
+        assert_eq!(loaded.stats(), history.stats());
+        assert_eq!(loaded.branches(), history.branches());
+        assert_eq!(loaded.get_undo_entry(), history.get_undo_entry());
+    }
+
+    #[test]
+    fn test_load_recomputes_size() {
+        let mut history = EditorHistory::default();
+        insert(&mut history, 0, "ACGT");
+        let sequence = nucleotides("ACGT").into_iter().collect();
+
+        // Files written before the size was skipped still contain it
+        let mut json = serde_json::to_value(history.save(&sequence)).unwrap();
+        json["history"]["size_in_bytes"] = 0.into();
+        let saved = serde_json::from_value::<SavedHistory>(json).unwrap();
+
+        let mut loaded = EditorHistory::default();
+        assert!(loaded.load(saved, &sequence));
+        assert_ne!(loaded.stats().size_in_bytes, 0);
+        assert_eq!(loaded.stats(), history.stats());
+    }
+
+    #[test]
+    fn test_malformed_histories_are_rejected() {
+        let mut history = EditorHistory::default();
+        insert(&mut history, 0, "ACGT");
+        insert(&mut history, 4, "TT");
+        history.get_undo_entry();
+        let sequence = nucleotides("ACGT").into_iter().collect();
+        let json = serde_json::to_value(history.save(&sequence)).unwrap();
+
+        let loads = |change: &dyn Fn(&mut serde_json::Value)| {
+            let mut json = json.clone();
+            change(&mut json["history"]);
+            let saved = serde_json::from_value::<SavedHistory>(json).unwrap();
+            EditorHistory::default().load(saved, &sequence)
+        };
+        assert!(loads(&|_| {}));
+        // Redo moves to a node which doesn't exist
+        assert!(!loads(
+            &|history| history["nodes"]["1"]["redo_child"] = 7.into()
+        ));
+        // The root is its own grandparent
+        assert!(!loads(&|history| {
+            history["nodes"]["0"]["parent"] = 1.into();
+            history["nodes"]["1"]["children"] = vec![0, 2].into();
+        }));
+        // Undoing the insertion would delete more than there is
+        assert!(!loads(&|history| {
+            history["nodes"]["1"]["entry"]["operations"][0]["Insert"]["position"] = 3.into()
+        }));
+        // Redoing would insert behind the end
+        assert!(!loads(&|history| {
+            history["nodes"]["2"]["entry"]["operations"][0]["Insert"]["position"] = 5.into()
+        }));
+    }
 }
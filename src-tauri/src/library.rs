@@ -0,0 +1,236 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::PathBuf,
+};
+
+use parking_lot::RwLock;
+
+use crate::{
+    documents::DocumentId,
+    error::EditorError,
+    project::{project_file_name, read_projects, ProjectFile},
+    shared::LibraryItem,
+};
+
+/// Searchable collection of saved constructs, stored as project files in one directory.
+///
+/// Only the metadata is kept in memory, indexed by the words of names, tags,
+/// features, primers and notes.
+pub struct Library {
+    dir: PathBuf,
+    items: RwLock<BTreeMap<DocumentId, LibraryItem>>,
+    /// Maps every indexed word to the constructs containing it.
+    index: RwLock<BTreeMap<String, BTreeSet<DocumentId>>>,
+}
+
+impl Library {
+    pub fn open(dir: PathBuf) -> Self {
+        let library = Self {
+            dir,
+            items: RwLock::default(),
+            index: RwLock::default(),
+        };
+        for project in read_projects(&library.dir) {
+            library.insert(&project);
+        }
+        library
+    }
+
+    /// Adds a construct, replacing an earlier version with the same id.
+    pub fn add(&self, project: &ProjectFile) -> Result<LibraryItem, EditorError> {
+        fs::create_dir_all(&self.dir)?;
+        project.write(&self.path(&project.id))?;
+        self.remove_from_index(&project.id);
+        Ok(self.insert(project))
+    }
+
+    /// Returns `false` if the construct isn't in the library.
+    pub fn remove(&self, id: &str) -> Result<bool, EditorError> {
+        if !self.items.read().contains_key(id) {
+            return Ok(false);
+        }
+        fs::remove_file(self.path(id))?;
+        self.remove_from_index(id);
+        Ok(true)
+    }
+
+    pub fn read(&self, id: &str) -> Result<ProjectFile, EditorError> {
+        ProjectFile::read(&self.path(id))
+    }
+
+    /// Finds the constructs which contain every word of the query, matching
+    /// words by prefix and ignoring case. An empty query lists everything.
+    pub fn search(&self, query: &str) -> Vec<LibraryItem> {
+        let index = self.index.read();
+        let mut matches: Option<BTreeSet<DocumentId>> = None;
+        for term in words(query) {
+            let term_matches = index
+                .range(term.clone()..)
+                .take_while(|(word, _)| word.starts_with(&term))
+                .flat_map(|(_, ids)| ids.iter().cloned())
+                .collect::<BTreeSet<_>>();
+            matches = Some(match matches {
+                Some(matches) => matches.intersection(&term_matches).cloned().collect(),
+                None => term_matches,
+            });
+        }
+
+        let items = self.items.read();
+        match matches {
+            Some(ids) => ids.iter().filter_map(|id| items.get(id)).cloned().collect(),
+            None => items.values().cloned().collect(),
+        }
+    }
+
+    fn insert(&self, project: &ProjectFile) -> LibraryItem {
+        let info = &project.info;
+        let item = LibraryItem {
+            id: project.id.clone(),
+            name: info.name.clone(),
+            tags: info.tags.clone(),
+            feature_names: info
+                .features
+                .iter()
+                .map(|feature| feature.name.clone())
+                .collect(),
+            bp_count: project.sequence.len(),
+            updated_at: info.updated_at,
+        };
+
+        let texts = info
+            .name
+            .iter()
+            .chain(&info.tags)
+            .chain(
+                info.features
+                    .iter()
+                    .flat_map(|feature| [&feature.name, &feature.kind]),
+            )
+            .chain(info.primers.iter().map(|primer| &primer.name))
+            .chain([&info.notes]);
+        let mut index = self.index.write();
+        for word in texts.flat_map(|text| words(text)) {
+            index.entry(word).or_default().insert(project.id.clone());
+        }
+        self.items.write().insert(project.id.clone(), item.clone());
+        item
+    }
+
+    fn remove_from_index(&self, id: &str) {
+        self.items.write().remove(id);
+        let mut index = self.index.write();
+        index.retain(|_, ids| {
+            ids.remove(id);
+            !ids.is_empty()
+        });
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(project_file_name(id))
+    }
+}
+
+/// Splits text into lowercase words of letters and digits.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::Library;
+    use crate::{
+        editor::Editor,
+        project::{Feature, ProjectFile, ProjectInfo},
+        shared::Strand,
+    };
+
+    fn project(id: &str, name: &str, features: &[(&str, &str)], notes: &str) -> ProjectFile {
+        let info = ProjectInfo {
+            name: Some(name.to_string()),
+            features: features
+                .iter()
+                .map(|(name, kind)| Feature {
+                    name: name.to_string(),
+                    kind: kind.to_string(),
+                    start: 0,
+                    end: 0,
+                    strand: Strand::Forward,
+                })
+                .collect(),
+            notes: notes.to_string(),
+            ..ProjectInfo::default()
+        };
+        ProjectFile::new(id.to_string(), info, &Editor::default())
+    }
+
+    fn ids(library: &Library, query: &str) -> Vec<String> {
+        library
+            .search(query)
+            .into_iter()
+            .map(|item| item.id)
+            .collect()
+    }
+
+    #[test]
+    fn test_search() {
+        let dir = env::temp_dir().join("test_library_search");
+        let _ = fs::remove_dir_all(&dir);
+        let library = Library::open(dir.clone());
+        library
+            .add(&project(
+                "a",
+                "pET-28a",
+                &[("T7 promoter", "promoter"), ("KanR", "CDS")],
+                "",
+            ))
+            .unwrap();
+        library
+            .add(&project(
+                "b",
+                "pUC19",
+                &[("AmpR", "CDS"), ("lac promoter", "promoter")],
+                "",
+            ))
+            .unwrap();
+        library
+            .add(&project(
+                "c",
+                "pGEM-T",
+                &[("AmpR", "CDS"), ("T7 promoter", "promoter")],
+                "Cloned by Alex",
+            ))
+            .unwrap();
+
+        assert_eq!(ids(&library, "AmpR T7 promoter"), ["c"]);
+        assert_eq!(ids(&library, "amp"), ["b", "c"]);
+        assert_eq!(ids(&library, "alex"), ["c"]);
+        assert_eq!(ids(&library, ""), ["a", "b", "c"]);
+
+        // The library is rebuilt from the files on disk
+        assert!(library.remove("b").unwrap());
+        let library = Library::open(dir.clone());
+        assert_eq!(ids(&library, "promoter"), ["a", "c"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ids_stay_in_the_library() {
+        let dir = env::temp_dir().join("test_library_ids");
+        let _ = fs::remove_dir_all(&dir);
+        let library = Library::open(dir.join("library"));
+        for id in ["../outside", "pUC19.v2", "pUC19.v3"] {
+            library.add(&project(id, id, &[], "")).unwrap();
+        }
+
+        assert!(!dir.join("outside.plasmid").exists());
+        assert_eq!(fs::read_dir(dir.join("library")).unwrap().count(), 3);
+        assert_eq!(library.read("pUC19.v2").unwrap().id, "pUC19.v2");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,22 +3,84 @@
     windows_subsystem = "windows"
 )]
 
-use parking_lot::RwLock;
+use std::{mem, path::PathBuf, thread};
+
+use tauri::{ClipboardManager, Manager};
+
+mod analysis;
+use analysis::Analysis;
+
+mod autosave;
+use autosave::{Autosave, AUTOSAVE_INTERVAL};
+
+mod case_mask;
+
+mod checkpoint;
+
+mod clipboard;
+use clipboard::Clipboard;
+
+mod documents;
+use documents::{DocumentId, Documents};
 
 mod editor;
-use editor::{CursorMovement, Editor, SelectionMovement};
+use editor::{parse_nucleotides, CursorMovement, Editor, SelectionMovement};
+
+mod error;
+use error::EditorError;
 
 mod history;
+use history::{HistoryLimits, NodeId, SavedHistory};
+
+mod library;
+use library::Library;
+
+mod packed;
+
+mod paste;
+
+mod project;
+use project::{OpenedProject, ProjectFile, ProjectInfo, ProjectStore};
+
+mod search;
+use search::{parse_query, DistanceMetric, Motif, Pattern, PatternTarget, Peptide};
+
+mod sequence;
 
 mod shared;
-use shared::{CursorData, SequenceData, SequenceItem};
+use shared::{
+    AnalysisFinished, AnalysisProgress, ApproximateHit, CheckpointData, ClipboardPayload,
+    CursorData, DiffHunk, EditorChange, HistoryBranchData, HistoryNodeData, HistoryStats,
+    LibraryItem, PasteOptions, PasteResult, PasteSummary, SearchHit, SequenceData,
+    SequenceWindowData, Strand,
+};
+
+mod tasks;
+use tasks::{TaskId, TaskOutcome, Tasks};
 
 fn main() {
     tauri::Builder::default()
-        .manage(RwLock::new(Editor::default()))
+        .manage(Documents::default())
+        .manage(Tasks::default())
         .invoke_handler(tauri::generate_handler![
             initialize_editor,
+            close_document,
+            delete_document,
+            store_project,
+            list_documents,
+            set_project_info,
+            save_project,
+            open_project,
+            list_recovered_projects,
+            recover_project,
+            discard_recovered_project,
+            library_add,
+            library_remove,
+            library_search,
+            library_open,
+            save_history,
             calculate_sequence_data,
+            calculate_sequence_window,
             sequence_insert,
             sequence_insert_all,
             sequence_delete,
@@ -36,141 +98,901 @@ fn main() {
             expand_selection_left,
             expand_selection_right,
             get_selected_sequence,
+            clipboard_copy,
+            clipboard_cut,
+            clipboard_paste,
+            search_motif,
+            search_approximate,
+            search_peptide,
+            search_pattern,
+            start_analysis,
+            cancel_analysis,
+            list_running_analyses,
+            replace_next,
+            replace_all,
             undo,
             redo,
+            set_history_limits,
+            get_history_stats,
+            get_history_tree,
+            list_history_branches,
+            jump_to_history_node,
+            create_checkpoint,
+            list_checkpoints,
+            restore_checkpoint,
+            diff_checkpoint,
+            delete_checkpoint,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .setup(|app| {
+            let data_dir = app
+                .path_resolver()
+                .app_dir()
+                .unwrap_or_else(std::env::temp_dir);
+            app.manage(Autosave::new(data_dir.join("autosave")));
+            app.manage(ProjectStore::new(data_dir.join("projects")));
+            app.manage(Library::open(data_dir.join("library")));
+            app.manage(Clipboard::open(data_dir.join("clipboard.json")));
+
+            let handle = app.handle();
+            thread::spawn(move || loop {
+                thread::sleep(AUTOSAVE_INTERVAL);
+                handle
+                    .state::<Autosave>()
+                    .save_all(&handle.state::<Documents>());
+            });
+            Ok(())
+        })
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                let documents = app.state::<Documents>();
+                for document in documents.list() {
+                    // Nothing can be reported anymore, the autosave is the fallback
+                    let _ = store_document(&documents, &app.state(), &app.state(), &document);
+                }
+                app.state::<Autosave>().clear();
+            }
+        });
 }
 
-#[tauri::command]
-fn initialize_editor(state: tauri::State<RwLock<Editor>>, sequence: String) {
+/// Emitted with an `EditorChange` after every command which changed a document.
+const EDITOR_CHANGE_EVENT: &str = "editor-change";
+
+/// Runs an edit on a document and notifies the frontend of what changed.
+fn edit<R>(
+    app: &tauri::AppHandle,
+    documents: &Documents,
+    document: &str,
+    apply: impl FnOnce(&mut Editor) -> Result<R, EditorError>,
+) -> Result<R, EditorError> {
+    let state = documents.get(document)?;
     let mut state = state.write();
-    state.reset();
-    state.insert_all(sequence);
+    let before = (state.cursor_pos, state.selection.clone());
+    let result = apply(&mut state);
+
+    let changes = state.take_changes();
+    if !changes.is_empty() {
+        // Running analyses were started on the previous sequence
+        app.state::<Tasks>().cancel_document(document);
+    }
+    if !changes.is_empty() || before != (state.cursor_pos, state.selection.clone()) {
+        let change = EditorChange {
+            document: document.to_string(),
+            changes,
+            bp_count: state.sequence.len(),
+            cursor: CursorData {
+                position: state.cursor_pos,
+                is_at_end: state.cursor_pos == state.sequence.len(),
+            },
+            selection: state.selection.as_ref().map(|selection| selection.into()),
+        };
+        // Only fails if the window is gone, in which case nobody is listening
+        let _ = app.emit_all(EDITOR_CHANGE_EVENT, change);
+    }
+    result
+}
+
+/// Opens a document. Documents which are already open keep their state.
+///
+/// Without a `history`, the history stored by `store_project` is restored.
+/// Returns `false` if the history didn't match the sequence and was dropped.
+#[tauri::command]
+fn initialize_editor(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    store: tauri::State<ProjectStore>,
+    document: DocumentId,
+    sequence: String,
+    history: Option<SavedHistory>,
+) -> Result<bool, EditorError> {
+    if !documents.open(&document).1 {
+        return Ok(true);
+    }
+    let mut stored = store.read(&document).ok();
+    let checkpoints = stored
+        .as_mut()
+        .map(|project| mem::take(&mut project.checkpoints));
+    // The frontend only knows the uppercase letters, so a stored project of the
+    // same sequence is loaded instead to keep the case of its letters
+    let (sequence, history) = match (history, stored) {
+        (Some(history), _) => (sequence, Some(history)),
+        (None, Some(project)) if project.sequence.eq_ignore_ascii_case(&sequence) => {
+            (project.sequence, project.history)
+        }
+        // A history of another sequence is rejected by `load`, which reports it
+        (None, Some(project)) => (sequence, project.history),
+        (None, None) => (sequence, None),
+    };
+    let result = edit(&app, &documents, &document, |state| {
+        let history_restored = state.load(sequence, history)?;
+        if let Some(checkpoints) = checkpoints {
+            state.restore_checkpoints(checkpoints);
+        }
+        Ok(history_restored)
+    });
+    if result.is_err() {
+        // Otherwise the empty editor would count as initialized from now on
+        documents.close(&document);
+    }
+    result
+}
+
+/// Writes a document together with its history to the project store.
+fn store_document(
+    documents: &Documents,
+    autosave: &Autosave,
+    store: &ProjectStore,
+    document: &str,
+) -> Result<(), EditorError> {
+    let state = documents.get(document)?;
+    let project = ProjectFile::new(document.to_string(), autosave.info(document), &state.read());
+    store.write(&project)
+}
+
+/// Stores the document and releases its editor. Returns `false` if it wasn't open.
+#[tauri::command]
+fn close_document(
+    documents: tauri::State<Documents>,
+    autosave: tauri::State<Autosave>,
+    store: tauri::State<ProjectStore>,
+    tasks: tauri::State<Tasks>,
+    document: DocumentId,
+) -> Result<bool, EditorError> {
+    match store_document(&documents, &autosave, &store, &document) {
+        Ok(()) | Err(EditorError::UnknownDocument { .. }) => (),
+        Err(error) => return Err(error),
+    }
+    tasks.cancel_document(&document);
+    let closed = documents.close(&document);
+    autosave.remove(&document);
+    Ok(closed)
+}
+
+/// Closes a document without storing it and deletes its stored history.
+#[tauri::command]
+fn delete_document(
+    documents: tauri::State<Documents>,
+    autosave: tauri::State<Autosave>,
+    store: tauri::State<ProjectStore>,
+    tasks: tauri::State<Tasks>,
+    document: DocumentId,
+) -> bool {
+    tasks.cancel_document(&document);
+    let closed = documents.close(&document);
+    autosave.remove(&document);
+    store.remove(&document);
+    closed
+}
+
+/// Writes a document with its undo history to the app data directory.
+#[tauri::command]
+fn store_project(
+    documents: tauri::State<Documents>,
+    autosave: tauri::State<Autosave>,
+    store: tauri::State<ProjectStore>,
+    document: DocumentId,
+) -> Result<(), EditorError> {
+    store_document(&documents, &autosave, &store, &document)
+}
+
+#[tauri::command]
+fn list_documents(documents: tauri::State<Documents>) -> Vec<DocumentId> {
+    documents.list()
 }
 
 #[tauri::command]
-fn sequence_insert(state: tauri::State<RwLock<Editor>>, letter: char) {
-    state.write().insert(letter);
+fn set_project_info(autosave: tauri::State<Autosave>, document: DocumentId, info: ProjectInfo) {
+    autosave.set_info(document, info);
 }
 
 #[tauri::command]
-fn sequence_insert_all(state: tauri::State<RwLock<Editor>>, text: String) {
-    let text_without_whitespace = text.chars().filter(|c| !c.is_whitespace()).collect();
-    state.write().insert_all(text_without_whitespace);
+fn save_project(
+    documents: tauri::State<Documents>,
+    autosave: tauri::State<Autosave>,
+    document: DocumentId,
+    path: PathBuf,
+) -> Result<(), EditorError> {
+    let info = autosave.info(&document);
+    let state = documents.get(&document)?;
+    let project = ProjectFile::new(document, info, &state.read());
+    project.write(&path)
 }
 
+/// Opens a project file, replacing the document if it is already open.
 #[tauri::command]
-fn sequence_delete(state: tauri::State<RwLock<Editor>>) {
-    state.write().delete();
+fn open_project(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    autosave: tauri::State<Autosave>,
+    path: PathBuf,
+) -> Result<OpenedProject, EditorError> {
+    let project = ProjectFile::read(&path)?;
+    load_project(&app, &documents, &autosave, project)
 }
 
 #[tauri::command]
-fn sequence_delete_next(state: tauri::State<RwLock<Editor>>) {
-    state.write().delete_next();
+fn list_recovered_projects(autosave: tauri::State<Autosave>) -> Vec<(DocumentId, ProjectInfo)> {
+    autosave.recovered()
 }
 
 #[tauri::command]
-fn move_cursor(state: tauri::State<RwLock<Editor>>, index: usize) {
-    state.write().move_cursor(CursorMovement::To(index));
+fn recover_project(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    autosave: tauri::State<Autosave>,
+    document: DocumentId,
+) -> Result<Option<OpenedProject>, EditorError> {
+    match autosave.take_recovered(&document) {
+        Some(project) => load_project(&app, &documents, &autosave, project).map(Some),
+        None => Ok(None),
+    }
+}
+
+#[tauri::command]
+fn discard_recovered_project(autosave: tauri::State<Autosave>, document: DocumentId) -> bool {
+    autosave.discard_recovered(&document)
 }
 
+/// Saves a document to the library, replacing an earlier version.
 #[tauri::command]
-fn move_cursor_left(state: tauri::State<RwLock<Editor>>) {
-    state.write().move_cursor(CursorMovement::By(-1));
+fn library_add(
+    documents: tauri::State<Documents>,
+    autosave: tauri::State<Autosave>,
+    library: tauri::State<Library>,
+    document: DocumentId,
+) -> Result<LibraryItem, EditorError> {
+    let info = autosave.info(&document);
+    let state = documents.get(&document)?;
+    let project = ProjectFile::new(document, info, &state.read());
+    library.add(&project)
 }
 
 #[tauri::command]
-fn move_cursor_right(state: tauri::State<RwLock<Editor>>) {
-    state.write().move_cursor(CursorMovement::By(1));
+fn library_remove(library: tauri::State<Library>, id: DocumentId) -> Result<bool, EditorError> {
+    library.remove(&id)
 }
 
 #[tauri::command]
-fn move_cursor_to_start(state: tauri::State<RwLock<Editor>>) {
-    state.write().move_cursor(CursorMovement::Start);
+fn library_search(library: tauri::State<Library>, query: String) -> Vec<LibraryItem> {
+    library.search(&query)
 }
 
 #[tauri::command]
-fn move_cursor_to_end(state: tauri::State<RwLock<Editor>>) {
-    state.write().move_cursor(CursorMovement::End);
+fn library_open(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    autosave: tauri::State<Autosave>,
+    library: tauri::State<Library>,
+    id: DocumentId,
+) -> Result<OpenedProject, EditorError> {
+    let project = library.read(&id)?;
+    load_project(&app, &documents, &autosave, project)
+}
+
+fn load_project(
+    app: &tauri::AppHandle,
+    documents: &Documents,
+    autosave: &Autosave,
+    mut project: ProjectFile,
+) -> Result<OpenedProject, EditorError> {
+    let (_, is_new) = documents.open(&project.id);
+    let id = project.id.clone();
+    let history_restored = match edit(app, documents, &id, |state| project.load_into(state)) {
+        Ok(history_restored) => history_restored,
+        Err(error) => {
+            if is_new {
+                documents.close(&id);
+            }
+            return Err(error);
+        }
+    };
+    autosave.set_info(project.id.clone(), project.info.clone());
+    Ok(OpenedProject {
+        project,
+        history_restored,
+    })
 }
 
 #[tauri::command]
-fn move_cursor_to_codon_start(state: tauri::State<RwLock<Editor>>) {
-    state.write().move_cursor(CursorMovement::CodonStart);
+fn save_history(
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<SavedHistory, EditorError> {
+    let state = documents.get(&document)?;
+    let state = state.read();
+    Ok(state.history.save(&state.sequence))
 }
 
 #[tauri::command]
-fn move_cursor_to_codon_end(state: tauri::State<RwLock<Editor>>) {
-    state.write().move_cursor(CursorMovement::CodonEnd);
+fn sequence_insert(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    letter: char,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| state.insert(letter))
 }
 
 #[tauri::command]
-fn set_selection(state: tauri::State<RwLock<Editor>>, start: usize, end: usize) {
-    state
-        .write()
-        .move_selection(SelectionMovement::Set { start, end });
+fn sequence_insert_all(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    text: String,
+    options: Option<PasteOptions>,
+) -> Result<PasteSummary, EditorError> {
+    let (nucleotides, lowercase, summary) = paste::sanitize(&text, &options.unwrap_or_default())?;
+    edit(&app, &documents, &document, |state| {
+        state.paste(&nucleotides, &lowercase, None);
+        Ok(summary)
+    })
 }
 
 #[tauri::command]
-fn set_selection_all(state: tauri::State<RwLock<Editor>>) {
-    state.write().move_selection(SelectionMovement::All);
+fn sequence_delete(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| state.delete())
 }
 
 #[tauri::command]
-fn reset_selection(state: tauri::State<RwLock<Editor>>) {
-    state.write().move_selection(SelectionMovement::Reset);
+fn sequence_delete_next(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| state.delete_next())
 }
 
 #[tauri::command]
-fn expand_selection_left(state: tauri::State<RwLock<Editor>>) {
-    state
-        .write()
-        .move_selection(SelectionMovement::ExpandBy(-1));
+fn move_cursor(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    index: usize,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| {
+        state.move_cursor(CursorMovement::To(index))
+    })
 }
 
 #[tauri::command]
-fn expand_selection_right(state: tauri::State<RwLock<Editor>>) {
-    state.write().move_selection(SelectionMovement::ExpandBy(1));
+fn move_cursor_left(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| {
+        state.move_cursor(CursorMovement::By(-1))
+    })
 }
 
 #[tauri::command]
-fn get_selected_sequence(state: tauri::State<RwLock<Editor>>) -> String {
-    state.read().get_selected_sequence()
+fn move_cursor_right(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| {
+        state.move_cursor(CursorMovement::By(1))
+    })
 }
 
 #[tauri::command]
-fn undo(state: tauri::State<RwLock<Editor>>) {
-    state.write().undo();
+fn move_cursor_to_start(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| {
+        state.move_cursor(CursorMovement::Start)
+    })
 }
 
 #[tauri::command]
-fn redo(state: tauri::State<RwLock<Editor>>) {
-    state.write().redo();
+fn move_cursor_to_end(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| {
+        state.move_cursor(CursorMovement::End)
+    })
 }
 
 #[tauri::command]
-fn calculate_sequence_data(state: tauri::State<RwLock<Editor>>, force: bool) -> SequenceData {
+fn move_cursor_to_codon_start(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| {
+        state.move_cursor(CursorMovement::CodonStart)
+    })
+}
+
+#[tauri::command]
+fn move_cursor_to_codon_end(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| {
+        state.move_cursor(CursorMovement::CodonEnd)
+    })
+}
+
+#[tauri::command]
+fn set_selection(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    start: usize,
+    end: usize,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| {
+        state.move_selection(SelectionMovement::Set { start, end })
+    })
+}
+
+#[tauri::command]
+fn set_selection_all(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| {
+        state.move_selection(SelectionMovement::All)
+    })
+}
+
+#[tauri::command]
+fn reset_selection(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| {
+        state.move_selection(SelectionMovement::Reset)
+    })
+}
+
+#[tauri::command]
+fn expand_selection_left(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| {
+        state.move_selection(SelectionMovement::ExpandBy(-1))
+    })
+}
+
+#[tauri::command]
+fn expand_selection_right(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| {
+        state.move_selection(SelectionMovement::ExpandBy(1))
+    })
+}
+
+#[tauri::command]
+fn get_selected_sequence(
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<String, EditorError> {
+    Ok(documents.get(&document)?.read().get_selected_sequence())
+}
+
+/// Copies the selection. Other apps only receive the plain text, since the
+/// clipboard API of Tauri 1 is text-only. The returned payload is kept in
+/// the app data directory, so pasting it into the app keeps its origin and
+/// case, also after a restart.
+#[tauri::command]
+fn clipboard_copy(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    autosave: tauri::State<Autosave>,
+    clipboard: tauri::State<Clipboard>,
+    document: DocumentId,
+    strand: Strand,
+) -> Result<ClipboardPayload, EditorError> {
+    let state = documents.get(&document)?;
+    let info = autosave.info(&document);
+    let payload = Clipboard::payload(&state.read(), &info, document, strand, false)?;
+    app.clipboard_manager()
+        .write_text(payload.text.clone())
+        .map_err(clipboard_error)?;
+    clipboard.set(payload.clone(), None);
+    Ok(payload)
+}
+
+fn clipboard_error(error: impl ToString) -> EditorError {
+    EditorError::Clipboard {
+        message: error.to_string(),
+    }
+}
+
+/// Cuts the selection. The clipboard contents are the same as for `clipboard_copy`.
+#[tauri::command]
+fn clipboard_cut(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    autosave: tauri::State<Autosave>,
+    clipboard: tauri::State<Clipboard>,
+    document: DocumentId,
+    strand: Strand,
+) -> Result<ClipboardPayload, EditorError> {
+    let info = autosave.info(&document);
+    edit(&app, &documents, &document, |state| {
+        let payload = Clipboard::payload(state, &info, document.clone(), strand, true)?;
+        app.clipboard_manager()
+            .write_text(payload.text.clone())
+            .map_err(clipboard_error)?;
+        let cut_node = state.cut()?;
+        clipboard.set(payload.clone(), Some(cut_node));
+        Ok(payload)
+    })
+}
+
+/// Pastes the system clipboard. Fragments copied in the app are pasted as they
+/// were copied, any other text is cleaned up with the paste `options` first.
+///
+/// The features and primers of a pasted fragment are returned in positions of
+/// the document it was pasted into.
+#[tauri::command]
+fn clipboard_paste(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    clipboard: tauri::State<Clipboard>,
+    document: DocumentId,
+    options: Option<PasteOptions>,
+) -> Result<PasteResult, EditorError> {
+    let text = match app
+        .clipboard_manager()
+        .read_text()
+        .map_err(clipboard_error)?
+    {
+        Some(text) => text,
+        None => {
+            return Ok(PasteResult {
+                payload: None,
+                summary: None,
+            })
+        }
+    };
+    edit(&app, &documents, &document, |state| {
+        match clipboard.take_matching(&text) {
+            Some((mut payload, cut_node)) => {
+                let (nucleotides, lowercase) = parse_nucleotides(&payload.text)?;
+                let cut_node = cut_node.filter(|_| payload.source_document == document);
+                let position = state.paste(&nucleotides, &lowercase, cut_node);
+                Clipboard::rebase(&mut payload, position);
+                Ok(PasteResult {
+                    payload: Some(payload),
+                    summary: None,
+                })
+            }
+            None => {
+                let (nucleotides, lowercase, summary) =
+                    paste::sanitize(&text, &options.unwrap_or_default())?;
+                state.paste(&nucleotides, &lowercase, None);
+                Ok(PasteResult {
+                    payload: None,
+                    summary: Some(summary),
+                })
+            }
+        }
+    })
+}
+
+#[tauri::command]
+fn search_motif(
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    query: String,
+    circular: bool,
+) -> Result<Vec<SearchHit>, EditorError> {
+    let motif = parse_query(Motif::parse, &query)?;
+    Ok(documents
+        .get(&document)?
+        .read()
+        .find_motif(&motif, circular))
+}
+
+#[tauri::command]
+fn search_approximate(
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    query: String,
+    circular: bool,
+    metric: DistanceMetric,
+    max_distance: usize,
+) -> Result<Vec<ApproximateHit>, EditorError> {
+    let motif = parse_query(
+        |query| Motif::parse_approximate(query, max_distance),
+        &query,
+    )?;
+    Ok(documents
+        .get(&document)?
+        .read()
+        .find_approximate(&motif, circular, metric, max_distance))
+}
+
+#[tauri::command]
+fn search_peptide(
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    query: String,
+) -> Result<Vec<SearchHit>, EditorError> {
+    let peptide = parse_query(Peptide::parse, &query)?;
+    Ok(documents.get(&document)?.read().find_peptide(&peptide))
+}
+
+#[tauri::command]
+fn search_pattern(
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    query: String,
+    target: PatternTarget,
+) -> Result<Vec<SearchHit>, EditorError> {
+    let pattern = parse_query(Pattern::parse, &query)?;
+    Ok(documents
+        .get(&document)?
+        .read()
+        .find_pattern(&pattern, target))
+}
+
+/// Emitted with an `AnalysisProgress` while an analysis is running.
+const ANALYSIS_PROGRESS_EVENT: &str = "analysis-progress";
+
+/// Emitted with an `AnalysisFinished` once an analysis is done or cancelled.
+const ANALYSIS_FINISHED_EVENT: &str = "analysis-finished";
+
+/// Starts an analysis of a document in the background. Its progress and result
+/// are sent as events, and it is cancelled as soon as the sequence changes.
+#[tauri::command]
+fn start_analysis(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    tasks: tauri::State<Tasks>,
+    document: DocumentId,
+    analysis: Analysis,
+) -> Result<TaskId, EditorError> {
+    let state = documents.get(&document)?;
+    // Keep the editor locked until the task is registered, so that no edit
+    // can slip in without cancelling it
+    let state = state.read();
+    let prepared = analysis.prepare(&state)?;
+
+    let (progress_app, progress_document) = (app.clone(), document.clone());
+    let finished_document = document.clone();
+    Ok(tasks.spawn(
+        document,
+        move |task, done, total| {
+            let progress = AnalysisProgress {
+                task,
+                document: progress_document.clone(),
+                done,
+                total,
+            };
+            let _ = progress_app.emit_all(ANALYSIS_PROGRESS_EVENT, progress);
+        },
+        prepared,
+        move |task, outcome| {
+            let finished = AnalysisFinished {
+                task,
+                document: finished_document,
+                failed: outcome == TaskOutcome::Failed,
+                result: match outcome {
+                    TaskOutcome::Done(result) => Some(result),
+                    TaskOutcome::Cancelled | TaskOutcome::Failed => None,
+                },
+            };
+            let _ = app.emit_all(ANALYSIS_FINISHED_EVENT, finished);
+        },
+    ))
+}
+
+/// Returns `false` if the analysis isn't running anymore.
+#[tauri::command]
+fn cancel_analysis(tasks: tauri::State<Tasks>, task: TaskId) -> bool {
+    tasks.cancel(task)
+}
+
+#[tauri::command]
+fn list_running_analyses(tasks: tauri::State<Tasks>, document: DocumentId) -> Vec<TaskId> {
+    tasks.running(&document)
+}
+
+#[tauri::command]
+fn replace_next(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    query: String,
+    replacement: String,
+    strand_aware: bool,
+) -> Result<Option<SearchHit>, EditorError> {
+    let motif = parse_query(Motif::parse, &query)?;
+    edit(&app, &documents, &document, |state| {
+        state.replace_next(&motif, &replacement, strand_aware)
+    })
+}
+
+#[tauri::command]
+fn replace_all(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    query: String,
+    replacement: String,
+    strand_aware: bool,
+) -> Result<usize, EditorError> {
+    let motif = parse_query(Motif::parse, &query)?;
+    edit(&app, &documents, &document, |state| {
+        state.replace_all(&motif, &replacement, strand_aware)
+    })
+}
+
+#[tauri::command]
+fn undo(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| state.undo())
+}
+
+#[tauri::command]
+fn redo(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| state.redo())
+}
+
+#[tauri::command]
+fn set_history_limits(
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    limits: HistoryLimits,
+) -> Result<(), EditorError> {
+    documents.get(&document)?.write().history.set_limits(limits);
+    Ok(())
+}
+
+#[tauri::command]
+fn get_history_stats(
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<HistoryStats, EditorError> {
+    Ok(documents.get(&document)?.read().history.stats())
+}
+
+#[tauri::command]
+fn get_history_tree(
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<Vec<HistoryNodeData>, EditorError> {
+    Ok(documents.get(&document)?.read().history.nodes())
+}
+
+#[tauri::command]
+fn list_history_branches(
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<Vec<HistoryBranchData>, EditorError> {
+    Ok(documents.get(&document)?.read().history.branches())
+}
+
+#[tauri::command]
+fn jump_to_history_node(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    id: NodeId,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| {
+        state.jump_to_history_node(id)
+    })
+}
+
+#[tauri::command]
+fn create_checkpoint(
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    name: String,
+) -> Result<CheckpointData, EditorError> {
+    Ok(documents.get(&document)?.write().create_checkpoint(name))
+}
+
+#[tauri::command]
+fn list_checkpoints(
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+) -> Result<Vec<CheckpointData>, EditorError> {
+    Ok(documents.get(&document)?.read().checkpoints.list())
+}
+
+#[tauri::command]
+fn restore_checkpoint(
+    app: tauri::AppHandle,
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    id: usize,
+) -> Result<(), EditorError> {
+    edit(&app, &documents, &document, |state| {
+        state.restore_checkpoint(id)
+    })
+}
+
+#[tauri::command]
+fn diff_checkpoint(
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    id: usize,
+) -> Result<Vec<DiffHunk>, EditorError> {
+    documents.get(&document)?.read().diff_checkpoint(id)
+}
+
+#[tauri::command]
+fn delete_checkpoint(
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    id: usize,
+) -> Result<bool, EditorError> {
+    Ok(documents.get(&document)?.write().checkpoints.remove(id))
+}
+
+#[tauri::command]
+fn calculate_sequence_data(
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    force: bool,
+    packed: Option<bool>,
+) -> Result<SequenceData, EditorError> {
+    let state = documents.get(&document)?;
     let data = {
         if force || state.read().sequence_dirty {
-            let mut data: Vec<SequenceItem> = Vec::with_capacity(state.read().codons.len());
             state.write().update();
-            for (index, codon) in state.read().codons.iter().enumerate() {
-                data.push(SequenceItem {
-                    codon: codon.nucleotides.clone(),
-                    anticodon: codon.anti_nucleotides.clone(),
-                    peptide: codon.peptide,
-                    start_index: index * 3,
-                })
-            }
-            Some(data)
+            let (_, items) = state
+                .read()
+                .sequence_items(0, usize::MAX, packed.unwrap_or(false));
+            Some(items)
         } else {
             None
         }
     };
     let state = state.read();
-    SequenceData {
+    Ok(SequenceData {
         sequence: data,
         bp_count: state.sequence.len(),
         cursor: CursorData {
@@ -178,5 +1000,33 @@ fn calculate_sequence_data(state: tauri::State<RwLock<Editor>>, force: bool) ->
             is_at_end: state.cursor_pos == state.sequence.len(),
         },
         selection: state.selection.as_ref().map(|selection| selection.into()),
+    })
+}
+
+/// Like `calculate_sequence_data`, but only for the codons overlapping the
+/// nucleotide window `start..end`, so that large sequences can be rendered lazily.
+#[tauri::command]
+fn calculate_sequence_window(
+    documents: tauri::State<Documents>,
+    document: DocumentId,
+    start: usize,
+    end: usize,
+    packed: Option<bool>,
+) -> Result<SequenceWindowData, EditorError> {
+    let state = documents.get(&document)?;
+    if state.read().sequence_dirty {
+        state.write().update();
     }
+    let state = state.read();
+    let (start_index, items) = state.sequence_items(start, end, packed.unwrap_or(false));
+    Ok(SequenceWindowData {
+        start_index,
+        items,
+        bp_count: state.sequence.len(),
+        cursor: CursorData {
+            position: state.cursor_pos,
+            is_at_end: state.cursor_pos == state.sequence.len(),
+        },
+        selection: state.selection.as_ref().map(|selection| selection.into()),
+    })
 }
@@ -0,0 +1,232 @@
+use std::ops::Range;
+
+use plasmid::{
+    traits::{ToLetter, TryFromLetter},
+    uni::IupacNucleotide,
+};
+
+use crate::search::base_mask;
+
+/// IUPAC letter for every 4-bit base mask, see [`base_mask`].
+const LETTERS: [char; 16] = [
+    '-', 'A', 'C', 'M', 'G', 'R', 'S', 'V', 'T', 'W', 'Y', 'H', 'K', 'D', 'B', 'N',
+];
+
+/// Base masks of A, C, G and T, indexed by their 2-bit code.
+const BASES: [u8; 4] = [0b0001, 0b0010, 0b0100, 0b1000];
+
+/// Nucleotides packed into 4 bits each, or 2 bits each if the nucleotides are
+/// all unambiguous bases.
+///
+/// The 4-bit codes are the base masks used by the search, so search indexes can
+/// be built without decoding the nucleotides.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PackedNucleotides {
+    bytes: Vec<u8>,
+    len: usize,
+    /// Whether `bytes` holds 2-bit codes of A, C, G and T only.
+    bases_only: bool,
+}
+
+impl PackedNucleotides {
+    pub fn from_masks(masks: &[u8]) -> Self {
+        let bases_only = masks.iter().all(|&mask| BASES.contains(&mask));
+        let bytes = if bases_only {
+            masks
+                .chunks(4)
+                .map(|group| {
+                    group.iter().enumerate().fold(0, |byte, (index, &mask)| {
+                        byte | (mask.trailing_zeros() as u8) << (index * 2)
+                    })
+                })
+                .collect()
+        } else {
+            masks
+                .chunks(2)
+                .map(|group| {
+                    group.iter().enumerate().fold(0, |byte, (index, &mask)| {
+                        byte | (mask & 0b1111) << (index * 4)
+                    })
+                })
+                .collect()
+        };
+        Self {
+            bytes,
+            len: masks.len(),
+            bases_only,
+        }
+    }
+
+    pub fn from_nucleotides(nucleotides: &[IupacNucleotide]) -> Self {
+        Self::from_masks(&nucleotides.iter().map(encode).collect::<Vec<_>>())
+    }
+
+    /// The nucleotides of `self` followed by those of `other`.
+    pub fn concat(&self, other: &Self) -> Self {
+        let masks = self
+            .masks(0..self.len)
+            .chain(other.masks(0..other.len))
+            .collect::<Vec<_>>();
+        Self::from_masks(&masks)
+    }
+
+    /// Packs several parts into one by copying their codes, without unpacking
+    /// them into base masks first. Parts of the same width which start on a
+    /// byte boundary are copied byte by byte.
+    pub fn join(parts: &[PackedNucleotides]) -> Self {
+        let len = parts.iter().map(|part| part.len).sum::<usize>();
+        let bases_only = parts.iter().all(|part| part.bases_only);
+        let bits = if bases_only { 2 } else { 4 };
+        let mut bytes = vec![0u8; (len * bits + 7) / 8];
+        let mut position = 0;
+        for part in parts {
+            if part.bases_only == bases_only && position * bits % 8 == 0 {
+                let start = position * bits / 8;
+                bytes[start..start + part.bytes.len()].copy_from_slice(&part.bytes);
+            } else {
+                for index in 0..part.len {
+                    let code = if bases_only {
+                        part.bytes[index / 4] >> (index % 4 * 2) & 0b11
+                    } else {
+                        part.mask(index)
+                    };
+                    let bit = (position + index) * bits;
+                    bytes[bit / 8] |= code << (bit % 8);
+                }
+            }
+            position += part.len;
+        }
+        Self {
+            bytes,
+            len,
+            bases_only,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Base mask of the nucleotide at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub fn mask(&self, index: usize) -> u8 {
+        assert!(index < self.len, "index {} out of bounds", index);
+        if self.bases_only {
+            BASES[(self.bytes[index / 4] >> (index % 4 * 2) & 0b11) as usize]
+        } else {
+            self.bytes[index / 2] >> (index % 2 * 4) & 0b1111
+        }
+    }
+
+    pub fn masks(&self, range: Range<usize>) -> impl DoubleEndedIterator<Item = u8> + '_ {
+        range.map(move |index| self.mask(index))
+    }
+
+    pub fn nucleotides(
+        &self,
+        range: Range<usize>,
+    ) -> impl DoubleEndedIterator<Item = IupacNucleotide> + '_ {
+        self.masks(range).map(decode)
+    }
+
+    #[cfg(test)]
+    pub fn to_vec(&self) -> Vec<IupacNucleotide> {
+        self.nucleotides(0..self.len).collect()
+    }
+
+    /// Memory used on the heap.
+    pub fn heap_size(&self) -> usize {
+        self.bytes.capacity()
+    }
+}
+
+/// Base mask stored for a nucleotide.
+pub fn encode(nucleotide: &IupacNucleotide) -> u8 {
+    base_mask(nucleotide.to_letter()).unwrap_or(0)
+}
+
+pub fn decode(mask: u8) -> IupacNucleotide {
+    IupacNucleotide::try_from_letter(LETTERS[mask as usize & 0b1111])
+        .expect("every base mask has an IUPAC letter")
+}
+
+#[cfg(test)]
+mod tests {
+    use plasmid::{
+        traits::{ToLetter, TryFromLetter},
+        uni::IupacNucleotide,
+    };
+
+    use super::{decode, encode, PackedNucleotides, LETTERS};
+
+    fn pack(letters: &str) -> PackedNucleotides {
+        let masks = letters
+            .chars()
+            .map(|c| encode(&IupacNucleotide::try_from_letter(c).unwrap()))
+            .collect::<Vec<_>>();
+        PackedNucleotides::from_masks(&masks)
+    }
+
+    fn unpack(packed: &PackedNucleotides) -> String {
+        packed
+            .nucleotides(0..packed.len())
+            .map(|nuc| nuc.to_letter())
+            .collect()
+    }
+
+    #[test]
+    fn test_letters_round_trip() {
+        for (mask, &letter) in LETTERS.iter().enumerate() {
+            assert_eq!(encode(&decode(mask as u8)), mask as u8);
+            assert_eq!(decode(mask as u8).to_letter(), letter);
+        }
+    }
+
+    #[test]
+    fn test_packing() {
+        let bases = pack("ACGTTGCAG");
+        assert!(bases.bases_only);
+        assert_eq!(bases.bytes.len(), 3);
+        assert_eq!(unpack(&bases), "ACGTTGCAG");
+
+        let ambiguous = pack("ACGNRYK-T");
+        assert!(!ambiguous.bases_only);
+        assert_eq!(ambiguous.bytes.len(), 5);
+        assert_eq!(unpack(&ambiguous), "ACGNRYK-T");
+        assert_eq!(
+            ambiguous.masks(2..4).collect::<Vec<_>>(),
+            vec![0b0100, 0b1111]
+        );
+    }
+
+    #[test]
+    fn test_concat() {
+        let joined = pack("ACGT").concat(&pack("NA"));
+        assert!(!joined.bases_only);
+        assert_eq!(unpack(&joined), "ACGTNA");
+        assert_eq!(joined, pack("ACGTNA"));
+        assert_eq!(pack("").concat(&pack("TT")), pack("TT"));
+    }
+
+    #[test]
+    fn test_join() {
+        let parts = [pack("ACGT"), pack("TGA"), pack("CC"), pack("ACGTA")];
+        let joined = PackedNucleotides::join(&parts);
+        assert!(joined.bases_only);
+        assert_eq!(joined, pack("ACGTTGACCACGTA"));
+
+        let parts = [pack("ACG"), pack("NA"), pack("T"), pack("RYKT")];
+        let joined = PackedNucleotides::join(&parts);
+        assert!(!joined.bases_only);
+        assert_eq!(joined, pack("ACGNATRYKT"));
+        assert_eq!(PackedNucleotides::join(&[]), pack(""));
+    }
+}
@@ -0,0 +1,185 @@
+use plasmid::{traits::TryFromLetter, uni::IupacNucleotide};
+
+use crate::{
+    case_mask::CaseMask,
+    error::EditorError,
+    shared::{InvalidPolicy, PasteOptions, PasteSummary},
+};
+
+/// Turns pasted text into nucleotides according to the options, keeping
+/// track of which letters were lowercase.
+///
+/// Positions in errors are character positions in `text`.
+pub fn sanitize(
+    text: &str,
+    options: &PasteOptions,
+) -> Result<(Vec<IupacNucleotide>, CaseMask, PasteSummary), EditorError> {
+    let mut summary = PasteSummary::default();
+    let mut nucleotides = Vec::new();
+    let mut lowercase = Vec::new();
+
+    let origin = if options.genbank {
+        origin_block(text)
+    } else {
+        None
+    };
+    summary.genbank_origin = origin.is_some();
+    let strip_numbers = options.strip_numbers || origin.is_some();
+
+    // Character position of the start of the current line
+    let mut line_start = 0;
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        let position = line_start;
+        line_start += line.chars().count();
+        if let Some(lines) = &origin {
+            if !lines.contains(&index) {
+                continue;
+            }
+        }
+        if options.strip_fasta_headers && is_fasta_header(line) {
+            summary.stripped_headers += 1;
+            continue;
+        }
+
+        for (offset, character) in line.chars().enumerate() {
+            if character.is_whitespace() {
+                continue;
+            }
+            if strip_numbers && character.is_ascii_digit() {
+                summary.stripped_numbers += 1;
+                continue;
+            }
+            let letter = match character.to_ascii_uppercase() {
+                'U' if options.convert_rna => {
+                    summary.converted_rna += 1;
+                    'T'
+                }
+                letter => letter,
+            };
+            match IupacNucleotide::try_from_letter(letter) {
+                Ok(nucleotide) => {
+                    nucleotides.push(nucleotide);
+                    lowercase.push(character.is_ascii_lowercase());
+                }
+                Err(_) => match options.invalid {
+                    InvalidPolicy::Reject => {
+                        return Err(EditorError::InvalidCharacter {
+                            character,
+                            position: position + offset,
+                        })
+                    }
+                    InvalidPolicy::Skip => summary.skipped += 1,
+                    InvalidPolicy::ConvertToN => {
+                        summary.converted_to_n += 1;
+                        nucleotides.push(IupacNucleotide::N);
+                        lowercase.push(false);
+                    }
+                },
+            }
+        }
+    }
+
+    summary.inserted = nucleotides.len();
+    Ok((nucleotides, CaseMask::from_lowercase(lowercase), summary))
+}
+
+fn is_fasta_header(line: &str) -> bool {
+    matches!(line.trim_start().chars().next(), Some('>' | ';'))
+}
+
+/// Indices of the lines between a GenBank `ORIGIN` line and the `//` ending the record.
+fn origin_block(text: &str) -> Option<std::ops::Range<usize>> {
+    let mut lines = text.lines().enumerate();
+    let (origin, _) = lines.find(|(_, line)| line.starts_with("ORIGIN"))?;
+    let end = lines
+        .find(|(_, line)| line.trim_start().starts_with("//"))
+        .map_or(usize::MAX, |(index, _)| index);
+    Some(origin + 1..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sanitize, InvalidPolicy, PasteOptions, PasteSummary};
+    use crate::{case_mask::cased_letter, error::EditorError};
+
+    fn letters(text: &str, options: &PasteOptions) -> (String, PasteSummary) {
+        let (nucleotides, lowercase, summary) = sanitize(text, options).unwrap();
+        let letters = nucleotides
+            .iter()
+            .enumerate()
+            .map(|(position, n)| cased_letter(n, lowercase.is_lowercase(position)))
+            .collect();
+        (letters, summary)
+    }
+
+    #[test]
+    fn test_strict_by_default() {
+        let options = PasteOptions::default();
+        assert_eq!(letters("acg t\nRN", &options).0, "acgtRN");
+        assert_eq!(
+            sanitize("AC\n1 GU", &options),
+            Err(EditorError::InvalidCharacter {
+                character: '1',
+                position: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_fasta_and_rna() {
+        let options = PasteOptions {
+            strip_fasta_headers: true,
+            convert_rna: true,
+            ..PasteOptions::default()
+        };
+        let (sequence, summary) = letters(">seq1 mRNA\nAUGgcu\n;comment\nUAA\n", &options);
+        assert_eq!(sequence, "ATGgctTAA");
+        assert_eq!(
+            summary,
+            PasteSummary {
+                inserted: 9,
+                stripped_headers: 2,
+                converted_rna: 3,
+                ..PasteSummary::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_genbank_origin() {
+        let record = "LOCUS       pTEST  12 bp    DNA\n\
+                      FEATURES             Location/Qualifiers\n\
+                      ORIGIN\n        1 gaattcatgc\n       11 at\n//\n";
+        let options = PasteOptions {
+            genbank: true,
+            ..PasteOptions::default()
+        };
+        let (sequence, summary) = letters(record, &options);
+        assert_eq!(sequence, "gaattcatgcat");
+        assert!(summary.genbank_origin);
+        assert_eq!(summary.stripped_numbers, 3);
+
+        // Without an ORIGIN block, the text is pasted as is
+        assert_eq!(letters("ACGT", &options).0, "ACGT");
+    }
+
+    #[test]
+    fn test_invalid_policies() {
+        let skip = PasteOptions {
+            invalid: InvalidPolicy::Skip,
+            ..PasteOptions::default()
+        };
+        let (sequence, summary) = letters("AC*G!T", &skip);
+        assert_eq!(sequence, "ACGT");
+        assert_eq!(summary.skipped, 2);
+
+        let convert = PasteOptions {
+            invalid: InvalidPolicy::ConvertToN,
+            strip_numbers: true,
+            ..PasteOptions::default()
+        };
+        let (sequence, summary) = letters("ac*g 12 T", &convert);
+        assert_eq!(sequence, "acNgT");
+        assert_eq!((summary.converted_to_n, summary.stripped_numbers), (1, 2));
+    }
+}
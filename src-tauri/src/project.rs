@@ -0,0 +1,349 @@
+use std::{
+    fs, mem,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    checkpoint::Checkpoints, documents::DocumentId, editor::Editor, error::EditorError,
+    history::SavedHistory, shared::Strand,
+};
+
+/// Version of the project file format. Files with a newer version are rejected.
+pub const PROJECT_FORMAT_VERSION: u32 = 2;
+
+pub const PROJECT_FILE_EXTENSION: &str = "plasmid";
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    Linear,
+    Circular,
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Topology::Linear
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Feature {
+    pub name: String,
+    pub kind: String,
+    pub start: usize,
+    pub end: usize,
+    pub strand: Strand,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Primer {
+    pub name: String,
+    pub sequence: String,
+    /// Where the primer binds, if it was placed on the sequence.
+    pub start: Option<usize>,
+    pub strand: Strand,
+}
+
+/// Everything about a project except its sequence and history, which are owned by the editor.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ProjectInfo {
+    pub name: Option<String>,
+    /// Milliseconds since the unix epoch.
+    pub created_at: u64,
+    /// Milliseconds since the unix epoch.
+    pub updated_at: Option<u64>,
+    pub topology: Topology,
+    pub features: Vec<Feature>,
+    pub primers: Vec<Primer>,
+    pub notes: String,
+    pub tags: Vec<String>,
+}
+
+/// A project as it is stored on disk.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct ProjectFile {
+    pub version: u32,
+    pub id: DocumentId,
+    #[serde(flatten)]
+    pub info: ProjectInfo,
+    pub sequence: String,
+    pub history: Option<SavedHistory>,
+    /// Missing in files of version 1.
+    #[serde(default)]
+    pub checkpoints: Checkpoints,
+}
+
+#[derive(serde::Deserialize)]
+struct FormatVersion {
+    version: u32,
+}
+
+impl ProjectFile {
+    pub fn new(id: DocumentId, info: ProjectInfo, editor: &Editor) -> Self {
+        Self {
+            version: PROJECT_FORMAT_VERSION,
+            id,
+            info,
+            sequence: editor.sequence.letters(0..editor.sequence.len()),
+            history: Some(editor.history.save(&editor.sequence)),
+            checkpoints: editor.checkpoints.clone(),
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Self, EditorError> {
+        let json = fs::read_to_string(path)?;
+        let FormatVersion { version } = serde_json::from_str(&json)?;
+        if version > PROJECT_FORMAT_VERSION {
+            return Err(EditorError::UnsupportedVersion { version });
+        }
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Writes the file through a temporary file next to it, so that a crash
+    /// while saving doesn't leave a truncated project behind.
+    ///
+    /// The temporary file gets a `.tmp` suffix instead of replacing the
+    /// extension, so that it can't overwrite another file with the same stem.
+    pub fn write(&self, path: &Path) -> Result<(), EditorError> {
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        let temp_path = PathBuf::from(temp_path);
+        fs::write(&temp_path, self.to_json()?)?;
+        Ok(fs::rename(temp_path, path)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, EditorError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Replaces the content of an editor with the project. The history and
+    /// checkpoints are moved into the editor, so they aren't sent along with
+    /// the project afterwards. Malformed checkpoints are dropped.
+    ///
+    /// Returns `false` if the saved history didn't match the sequence and was dropped.
+    pub fn load_into(&mut self, editor: &mut Editor) -> Result<bool, EditorError> {
+        let history_restored = editor.load(self.sequence.clone(), self.history.take())?;
+        editor.restore_checkpoints(mem::take(&mut self.checkpoints));
+        Ok(history_restored)
+    }
+}
+
+/// A project which was loaded into an editor.
+#[derive(serde::Serialize, Debug)]
+pub struct OpenedProject {
+    #[serde(flatten)]
+    pub project: ProjectFile,
+    /// Whether the saved undo history could be restored.
+    pub history_restored: bool,
+}
+
+/// Project files in the app data directory, which keep the undo history of
+/// projects between sessions.
+///
+/// Unlike autosaved files, they are only written on an explicit save, when a
+/// document is closed and when the app exits.
+pub struct ProjectStore {
+    dir: PathBuf,
+}
+
+impl ProjectStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn write(&self, project: &ProjectFile) -> Result<(), EditorError> {
+        fs::create_dir_all(&self.dir)?;
+        project.write(&self.path(&project.id))
+    }
+
+    pub fn read(&self, id: &str) -> Result<ProjectFile, EditorError> {
+        ProjectFile::read(&self.path(id))
+    }
+
+    /// Deletes the file of a project, if there is one.
+    pub fn remove(&self, id: &str) {
+        let _ = fs::remove_file(self.path(id));
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(project_file_name(id))
+    }
+}
+
+/// Name of the file a project is stored in within one of the app's directories.
+///
+/// Everything except lowercase ASCII letters, digits, `-` and `_` is
+/// percent-encoded, so that an id can't point outside the directory, and ids
+/// which only differ after a dot or in case get different files.
+pub fn project_file_name(id: &str) -> String {
+    let mut name = String::with_capacity(id.len());
+    for byte in id.bytes() {
+        match byte {
+            b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' => name.push(byte as char),
+            _ => name.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    format!("{}.{}", name, PROJECT_FILE_EXTENSION)
+}
+
+/// Reads all project files in a directory. Unreadable files are skipped.
+pub fn read_projects(dir: &Path) -> Vec<ProjectFile> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .map_or(false, |ext| ext == PROJECT_FILE_EXTENSION)
+        })
+        .filter_map(|path| ProjectFile::read(&path).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::{
+        project_file_name, ProjectFile, ProjectInfo, ProjectStore, Topology,
+        PROJECT_FILE_EXTENSION, PROJECT_FORMAT_VERSION,
+    };
+    use crate::{editor::Editor, error::EditorError};
+
+    #[test]
+    fn test_write_and_read() {
+        let mut editor = Editor::default();
+        editor.insert_all("ACgt".to_string()).unwrap();
+        editor.insert_all("TT".to_string()).unwrap();
+        let info = ProjectInfo {
+            name: Some("pUC19".to_string()),
+            topology: Topology::Circular,
+            ..ProjectInfo::default()
+        };
+
+        let path = env::temp_dir().join("test_write_and_read.plasmid");
+        ProjectFile::new("project".to_string(), info.clone(), &editor)
+            .write(&path)
+            .unwrap();
+        let mut project = ProjectFile::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(project.info, info);
+        assert_eq!(project.sequence, "ACgtTT");
+
+        let mut loaded = Editor::default();
+        assert!(project.load_into(&mut loaded).unwrap());
+        assert_eq!(loaded.sequence, editor.sequence);
+        loaded.undo().unwrap();
+        assert_eq!(loaded.sequence.len(), 4);
+    }
+
+    #[test]
+    fn test_write_keeps_files_with_the_same_stem() {
+        let dir = env::temp_dir().join("test_write_keeps_files_with_the_same_stem");
+        fs::create_dir_all(&dir).unwrap();
+        let other = dir.join("project.tmp");
+        fs::write(&other, "unrelated").unwrap();
+
+        let path = dir.join("project.plasmid");
+        ProjectFile::new(
+            "project".to_string(),
+            ProjectInfo::default(),
+            &Editor::default(),
+        )
+        .write(&path)
+        .unwrap();
+        assert_eq!(fs::read_to_string(&other).unwrap(), "unrelated");
+        assert!(ProjectFile::read(&path).is_ok());
+        assert!(!dir.join("project.plasmid.tmp").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_store() {
+        let dir = env::temp_dir().join("test_project_store");
+        let store = ProjectStore::new(dir.clone());
+        let mut editor = Editor::default();
+        editor.insert_all("ACGT".to_string()).unwrap();
+
+        let project = ProjectFile::new("project".to_string(), ProjectInfo::default(), &editor);
+        store.write(&project).unwrap();
+        let history = store.read("project").unwrap().history;
+        assert!(history.is_some());
+
+        store.remove("project");
+        assert!(matches!(store.read("project"), Err(EditorError::Io { .. })));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_project_file_name() {
+        assert_eq!(
+            project_file_name("0f8e-4a1b_c"),
+            format!("0f8e-4a1b_c.{}", PROJECT_FILE_EXTENSION)
+        );
+        assert_eq!(project_file_name("../x"), "%2E%2E%2Fx.plasmid");
+        assert_eq!(project_file_name("/etc/passwd"), "%2Fetc%2Fpasswd.plasmid");
+        assert_ne!(project_file_name("pUC19.v2"), project_file_name("pUC19.v3"));
+        assert_ne!(project_file_name("puc19"), project_file_name("pUC19"));
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected() {
+        let path = env::temp_dir().join("test_newer_version_is_rejected.plasmid");
+        let json = format!(
+            r#"{{"version":{},"id":"project","sequence":"","history":null}}"#,
+            PROJECT_FORMAT_VERSION + 1
+        );
+        fs::write(&path, json).unwrap();
+        let result = ProjectFile::read(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            result,
+            Err(EditorError::UnsupportedVersion { version }) if version == PROJECT_FORMAT_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn test_checkpoints_are_kept() {
+        let mut editor = Editor::default();
+        editor.insert_all("ACgt".to_string()).unwrap();
+        let checkpoint = editor.create_checkpoint("lowercase".to_string());
+        editor.insert_all("TT".to_string()).unwrap();
+
+        let json = ProjectFile::new("project".to_string(), ProjectInfo::default(), &editor)
+            .to_json()
+            .unwrap();
+        let mut project: ProjectFile = serde_json::from_str(&json).unwrap();
+        let mut loaded = Editor::default();
+        assert!(project.load_into(&mut loaded).unwrap());
+        assert_eq!(loaded.checkpoints.list(), editor.checkpoints.list());
+        assert_eq!(loaded.checkpoints.list().len(), 1);
+
+        loaded.restore_checkpoint(checkpoint.id).unwrap();
+        assert_eq!(loaded.sequence.letters(0..loaded.sequence.len()), "ACgt");
+    }
+
+    #[test]
+    fn test_version_1_files_have_no_checkpoints() {
+        let json = r#"{"version":1,"id":"project","sequence":"ACGT","history":null}"#;
+        let mut project: ProjectFile = serde_json::from_str(json).unwrap();
+        let mut editor = Editor::default();
+        editor.create_checkpoint("before loading".to_string());
+        assert!(project.load_into(&mut editor).unwrap());
+        assert!(editor.checkpoints.list().is_empty());
+    }
+
+    #[test]
+    fn test_malformed_checkpoints_are_dropped() {
+        let json = r#"{"version":2,"id":"project","sequence":"ACGT","history":null,
+            "checkpoints":{"checkpoints":[{"id":0,"name":"a","created_at":0,
+            "snapshot":{"cursor_pos":9,"selection":null,"sequence":"AC"}}],"next_id":1}}"#;
+        let mut project: ProjectFile = serde_json::from_str(json).unwrap();
+        let mut editor = Editor::default();
+        assert!(project.load_into(&mut editor).unwrap());
+        assert!(editor.checkpoints.list().is_empty());
+    }
+}
@@ -0,0 +1,112 @@
+use plasmid::uni::IupacNucleotide;
+
+use crate::{
+    packed::{decode, PackedNucleotides},
+    shared::{translate_codon, Strand},
+};
+
+use super::iupac::complement_mask;
+
+/// One of the six reading frames of a sequence.
+///
+/// `offset` is counted from the 5' end of the respective strand. Frames with
+/// an offset other than 0, 1 or 2 are rejected when deserializing.
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "UncheckedReadingFrame")]
+pub struct ReadingFrame {
+    pub strand: Strand,
+    pub offset: usize,
+}
+
+#[derive(serde::Deserialize)]
+struct UncheckedReadingFrame {
+    strand: Strand,
+    offset: usize,
+}
+
+impl TryFrom<UncheckedReadingFrame> for ReadingFrame {
+    type Error = String;
+
+    fn try_from(frame: UncheckedReadingFrame) -> Result<Self, Self::Error> {
+        if frame.offset >= 3 {
+            return Err(format!(
+                "reading frame offset {} is not 0, 1 or 2",
+                frame.offset
+            ));
+        }
+        Ok(ReadingFrame {
+            strand: frame.strand,
+            offset: frame.offset,
+        })
+    }
+}
+
+impl ReadingFrame {
+    pub fn all() -> [ReadingFrame; 6] {
+        let frame = |strand, offset| ReadingFrame { strand, offset };
+        [
+            frame(Strand::Forward, 0),
+            frame(Strand::Forward, 1),
+            frame(Strand::Forward, 2),
+            frame(Strand::Reverse, 0),
+            frame(Strand::Reverse, 1),
+            frame(Strand::Reverse, 2),
+        ]
+    }
+
+    /// Translates every complete codon of the frame of a sequence.
+    /// Untranslatable codons are `None`.
+    pub fn translate(&self, sequence: &PackedNucleotides) -> Vec<Option<char>> {
+        let masks = sequence.masks(0..sequence.len());
+        let strand: Vec<IupacNucleotide> = match self.strand {
+            Strand::Forward => masks.map(decode).collect(),
+            Strand::Reverse => masks
+                .rev()
+                .map(|mask| decode(complement_mask(mask)))
+                .collect(),
+        };
+        strand
+            .get(self.offset..)
+            .unwrap_or_default()
+            .chunks_exact(3)
+            .map(translate_codon)
+            .collect()
+    }
+
+    /// Converts a range of codons of this frame into forward strand nucleotide coordinates.
+    pub fn nucleotide_range(
+        &self,
+        sequence_len: usize,
+        codon_start: usize,
+        codon_end: usize,
+    ) -> (usize, usize) {
+        let start = self.offset + codon_start * 3;
+        let end = self.offset + codon_end * 3;
+        match self.strand {
+            Strand::Forward => (start, end),
+            Strand::Reverse => (sequence_len - end, sequence_len - start),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReadingFrame;
+    use crate::shared::Strand;
+
+    #[test]
+    fn test_offset_is_checked() {
+        let frame: ReadingFrame =
+            serde_json::from_str(r#"{"strand":"Reverse","offset":2}"#).unwrap();
+        assert_eq!(
+            frame,
+            ReadingFrame {
+                strand: Strand::Reverse,
+                offset: 2
+            }
+        );
+        assert!(
+            serde_json::from_str::<ReadingFrame>(r#"{"strand":"Forward","offset":3}"#).is_err()
+        );
+    }
+}
@@ -0,0 +1,449 @@
+use crate::{packed::PackedNucleotides, shared::ApproximateHit};
+
+use super::{iupac::is_match, Motif, PROGRESS_INTERVAL};
+
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistanceMetric {
+    /// Substitutions only.
+    Hamming,
+    /// Substitutions, insertions and deletions.
+    Edit,
+}
+
+impl Motif {
+    /// Parses a query for an approximate search like [`Motif::parse`].
+    ///
+    /// Also returns `None` if `max_distance` isn't smaller than the motif,
+    /// since every position of the sequence would match it.
+    pub fn parse_approximate(query: &str, max_distance: usize) -> Option<Self> {
+        Self::parse(query).filter(|motif| max_distance < motif.len())
+    }
+
+    /// Finds all occurrences within `max_distance` on both strands,
+    /// ranked by distance and then by position. The motif has to be parsed
+    /// with [`Motif::parse_approximate`] for that distance.
+    pub fn find_approximate(
+        &self,
+        sequence: &PackedNucleotides,
+        circular: bool,
+        metric: DistanceMetric,
+        max_distance: usize,
+    ) -> Vec<ApproximateHit> {
+        self.find_approximate_with_progress(
+            sequence,
+            circular,
+            metric,
+            max_distance,
+            &mut |_, _| true,
+        )
+        .unwrap_or_default()
+    }
+
+    /// Like [`Motif::find_approximate`], but calls `progress` with the number of
+    /// scanned and total positions every now and then. The search is aborted,
+    /// returning `None`, as soon as `progress` returns `false`.
+    pub fn find_approximate_with_progress(
+        &self,
+        sequence: &PackedNucleotides,
+        circular: bool,
+        metric: DistanceMetric,
+        max_distance: usize,
+        progress: &mut dyn FnMut(usize, usize) -> bool,
+    ) -> Option<Vec<ApproximateHit>> {
+        if sequence.is_empty() {
+            return Some(Vec::new());
+        }
+
+        let patterns = self.patterns();
+        let total = patterns.len() * sequence.len();
+        let mut hits = Vec::new();
+        for (round, (pattern, strand)) in patterns.into_iter().enumerate() {
+            let mut report =
+                |done: usize| progress(round * sequence.len() + done.min(sequence.len()), total);
+            let matches = match metric {
+                DistanceMetric::Hamming => {
+                    find_hamming(pattern, sequence, circular, max_distance, &mut report)?
+                }
+                DistanceMetric::Edit => {
+                    find_edit(pattern, sequence, circular, max_distance, &mut report)?
+                }
+            };
+            hits.extend(
+                matches
+                    .into_iter()
+                    .map(|(start, end, distance)| ApproximateHit {
+                        start,
+                        end: if end > sequence.len() {
+                            end - sequence.len()
+                        } else {
+                            end
+                        },
+                        strand,
+                        distance,
+                    }),
+            );
+        }
+        hits.sort_by_key(|hit| (hit.distance, hit.start, hit.strand));
+        progress(total, total).then(|| hits)
+    }
+}
+
+/// Returns `(start, end, distance)` for every window with at most `max_distance` mismatches.
+fn find_hamming(
+    pattern: &[u8],
+    sequence: &PackedNucleotides,
+    circular: bool,
+    max_distance: usize,
+    report: &mut dyn FnMut(usize) -> bool,
+) -> Option<Vec<(usize, usize, usize)>> {
+    let len = sequence.len();
+    if pattern.len() > len {
+        return Some(Vec::new());
+    }
+    let last_start = if circular {
+        len - 1
+    } else {
+        len - pattern.len()
+    };
+
+    let mut matches = Vec::new();
+    for start in 0..=last_start {
+        if start % PROGRESS_INTERVAL == 0 && !report(start) {
+            return None;
+        }
+        let mut distance = 0;
+        for (offset, &query) in pattern.iter().enumerate() {
+            if !is_match(query, sequence.mask((start + offset) % len)) {
+                distance += 1;
+                if distance > max_distance {
+                    break;
+                }
+            }
+        }
+        if distance <= max_distance {
+            matches.push((start, start + pattern.len(), distance));
+        }
+    }
+    Some(matches)
+}
+
+/// Returns `(start, end, distance)` for the best alignment in every run of
+/// consecutive end positions within `max_distance` edits.
+fn find_edit(
+    pattern: &[u8],
+    sequence: &PackedNucleotides,
+    circular: bool,
+    max_distance: usize,
+    report: &mut dyn FnMut(usize) -> bool,
+) -> Option<Vec<(usize, usize, usize)>> {
+    let len = sequence.len();
+    if pattern.len() > len + max_distance {
+        return Some(Vec::new());
+    }
+
+    // Circular sequences are searched with the start appended again, so
+    // that alignments spanning the origin are found as well.
+    let text_len = if circular {
+        len + (pattern.len() + max_distance - 1).min(len)
+    } else {
+        len
+    };
+    let base = |position: usize| sequence.mask(position % len);
+    let text = (0..text_len).map(base);
+
+    let scores = if pattern.len() <= 64 {
+        myers_scores(pattern, text, report)?
+    } else {
+        dp_scores(pattern, text, report)?
+    };
+
+    let mut matches = Vec::new();
+    let mut best: Option<(usize, usize)> = None;
+    for (index, &score) in scores.iter().enumerate() {
+        if score <= max_distance {
+            if best.map_or(true, |(_, distance)| score < distance) {
+                best = Some((index + 1, score));
+            }
+            if index + 1 < scores.len() {
+                continue;
+            }
+        }
+        if let Some((end, distance)) = best.take() {
+            let start = alignment_start(pattern, &base, end, distance);
+            if start < len && end - start <= len {
+                matches.push((start, end, distance));
+            }
+        }
+    }
+    Some(matches)
+}
+
+/// Myers' bit-parallel algorithm. Returns, for every end position in `text`,
+/// the smallest edit distance of `pattern` to a substring ending there.
+fn myers_scores(
+    pattern: &[u8],
+    text: impl ExactSizeIterator<Item = u8>,
+    report: &mut dyn FnMut(usize) -> bool,
+) -> Option<Vec<usize>> {
+    let mut peq = [0u64; 16];
+    for (mask, eq) in peq.iter_mut().enumerate() {
+        for (index, &query) in pattern.iter().enumerate() {
+            if is_match(query, mask as u8) {
+                *eq |= 1 << index;
+            }
+        }
+    }
+
+    let high = 1u64 << (pattern.len() - 1);
+    let mut pv = !0u64;
+    let mut mv = 0u64;
+    let mut score = pattern.len();
+    let mut scores = Vec::with_capacity(text.len());
+    for (position, base) in text.enumerate() {
+        if position % PROGRESS_INTERVAL == 0 && !report(position) {
+            return None;
+        }
+        let eq = peq[base as usize & 0xf];
+        let xv = eq | mv;
+        let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+        let mut ph = mv | !(xh | pv);
+        let mut mh = pv & xh;
+        if ph & high != 0 {
+            score += 1;
+        } else if mh & high != 0 {
+            score -= 1;
+        }
+        ph <<= 1;
+        mh <<= 1;
+        pv = mh | !(xv | ph);
+        mv = ph & xv;
+        scores.push(score);
+    }
+    Some(scores)
+}
+
+/// Plain dynamic programming fallback for patterns longer than a machine word.
+fn dp_scores(
+    pattern: &[u8],
+    text: impl ExactSizeIterator<Item = u8>,
+    report: &mut dyn FnMut(usize) -> bool,
+) -> Option<Vec<usize>> {
+    let mut column = (0..=pattern.len()).collect::<Vec<_>>();
+    let mut scores = Vec::with_capacity(text.len());
+    for (position, base) in text.enumerate() {
+        if position % PROGRESS_INTERVAL == 0 && !report(position) {
+            return None;
+        }
+        let mut diagonal = column[0];
+        for (index, &query) in pattern.iter().enumerate() {
+            let cost = usize::from(!is_match(query, base));
+            let value = (diagonal + cost)
+                .min(column[index] + 1)
+                .min(column[index + 1] + 1);
+            diagonal = column[index + 1];
+            column[index + 1] = value;
+        }
+        scores.push(column[pattern.len()]);
+    }
+    Some(scores)
+}
+
+/// Finds where an alignment of `pattern` ending at `end` of the text with
+/// the given distance starts, preferring alignments closest to the pattern length.
+fn alignment_start(
+    pattern: &[u8],
+    base: &dyn Fn(usize) -> u8,
+    end: usize,
+    distance: usize,
+) -> usize {
+    let window = (pattern.len() + distance).min(end);
+
+    // row[j]: distance between the processed pattern suffix and the last j bases of text
+    let mut row = (0..=window).collect::<Vec<_>>();
+    for (index, &query) in pattern.iter().rev().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = index + 1;
+        for j in 1..=window {
+            let cost = usize::from(!is_match(query, base(end - j)));
+            let value = (diagonal + cost).min(row[j] + 1).min(row[j - 1] + 1);
+            diagonal = row[j];
+            row[j] = value;
+        }
+    }
+
+    let length = (0..=window)
+        .filter(|&j| row[j] == distance)
+        .min_by_key(|&j| {
+            if j > pattern.len() {
+                j - pattern.len()
+            } else {
+                pattern.len() - j
+            }
+        })
+        .unwrap_or(pattern.len().min(end));
+    end - length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dp_scores, myers_scores, DistanceMetric};
+    use crate::error::EditorError;
+    use crate::packed::PackedNucleotides;
+    use crate::search::{iupac::base_mask, parse_query, Motif};
+    use crate::shared::{ApproximateHit, Strand};
+
+    fn base_masks(sequence: &str) -> Vec<u8> {
+        sequence.chars().map(|c| base_mask(c).unwrap()).collect()
+    }
+
+    fn masks(sequence: &str) -> PackedNucleotides {
+        PackedNucleotides::from_masks(&base_masks(sequence))
+    }
+
+    #[test]
+    fn test_myers_matches_dp() {
+        let pattern = base_masks("GATTACA");
+        let text = base_masks("TTGACTACAGGATTTACAGATACACC");
+        assert_eq!(
+            myers_scores(&pattern, text.iter().copied(), &mut |_| true),
+            dp_scores(&pattern, text.iter().copied(), &mut |_| true)
+        );
+    }
+
+    #[test]
+    fn test_parse_approximate() {
+        assert!(Motif::parse_approximate("GGATCC", 5).is_some());
+        assert_eq!(Motif::parse_approximate("GGATCC", 6), None);
+        assert_eq!(
+            parse_query(|query| Motif::parse_approximate(query, 3), "ACG"),
+            Err(EditorError::InvalidQuery {
+                query: "ACG".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_find_hamming() {
+        let motif = Motif::parse("GGATCC").unwrap();
+        let hits = motif.find_approximate(
+            &masks("AAGGATCCAAGGTTCCAA"),
+            false,
+            DistanceMetric::Hamming,
+            1,
+        );
+        assert_eq!(
+            hits,
+            vec![
+                ApproximateHit {
+                    start: 2,
+                    end: 8,
+                    strand: Strand::Forward,
+                    distance: 0
+                },
+                ApproximateHit {
+                    start: 10,
+                    end: 16,
+                    strand: Strand::Forward,
+                    distance: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_edit_with_indels() {
+        let motif = Motif::parse("ACGTACGTAC").unwrap();
+        let sequence = masks("TTTTACGTAACGTACTTTTCCCCCACGACGTACCCCC");
+
+        let hits = motif.find_approximate(&sequence, false, DistanceMetric::Edit, 1);
+        assert_eq!(
+            hits,
+            vec![
+                ApproximateHit {
+                    start: 4,
+                    end: 15,
+                    strand: Strand::Forward,
+                    distance: 1
+                },
+                ApproximateHit {
+                    start: 24,
+                    end: 33,
+                    strand: Strand::Forward,
+                    distance: 1
+                },
+            ]
+        );
+
+        let hits = motif.find_approximate(&sequence, false, DistanceMetric::Hamming, 1);
+        assert!(hits.is_empty());
+    }
+
+    #[test]
+    fn test_find_edit_reverse_strand() {
+        let motif = Motif::parse("AAACCCGGT").unwrap();
+        let hits = motif.find_approximate(&masks("TTACCGGTTTTT"), false, DistanceMetric::Edit, 1);
+        assert_eq!(
+            hits,
+            vec![ApproximateHit {
+                start: 2,
+                end: 10,
+                strand: Strand::Reverse,
+                distance: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn test_find_edit_across_origin() {
+        let motif = Motif::parse("GGGGAAAA").unwrap();
+        let sequence = masks("AATAACCCCCGGGG");
+        assert!(motif
+            .find_approximate(&sequence, false, DistanceMetric::Edit, 1)
+            .is_empty());
+        assert_eq!(
+            motif.find_approximate(&sequence, true, DistanceMetric::Edit, 1),
+            vec![ApproximateHit {
+                start: 10,
+                end: 4,
+                strand: Strand::Forward,
+                distance: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn test_progress_and_abort() {
+        let motif = Motif::parse("GAATTCA").unwrap();
+        let sequence = masks(&"ACGT".repeat(100_000));
+
+        let mut reports = Vec::new();
+        let hits = motif.find_approximate_with_progress(
+            &sequence,
+            false,
+            DistanceMetric::Edit,
+            1,
+            &mut |done, total| {
+                reports.push((done, total));
+                true
+            },
+        );
+        assert!(hits.is_some());
+        assert!(reports.len() > 2);
+        assert!(reports.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        assert_eq!(reports.last(), Some(&(800_000, 800_000)));
+
+        let mut calls = 0;
+        let hits = motif.find_approximate_with_progress(
+            &sequence,
+            false,
+            DistanceMetric::Hamming,
+            1,
+            &mut |_, _| {
+                calls += 1;
+                calls < 3
+            },
+        );
+        assert_eq!(hits, None);
+        assert_eq!(calls, 3);
+    }
+}
@@ -0,0 +1,78 @@
+pub const BASE_A: u8 = 0b0001;
+pub const BASE_C: u8 = 0b0010;
+pub const BASE_G: u8 = 0b0100;
+pub const BASE_T: u8 = 0b1000;
+
+/// Returns the set of bases an IUPAC letter stands for, encoded as a bit mask.
+///
+/// Gaps are represented by an empty mask.
+pub fn base_mask(letter: char) -> Option<u8> {
+    let mask = match letter.to_ascii_uppercase() {
+        'A' => BASE_A,
+        'C' => BASE_C,
+        'G' => BASE_G,
+        'T' | 'U' => BASE_T,
+        'R' => BASE_A | BASE_G,
+        'Y' => BASE_C | BASE_T,
+        'S' => BASE_C | BASE_G,
+        'W' => BASE_A | BASE_T,
+        'K' => BASE_G | BASE_T,
+        'M' => BASE_A | BASE_C,
+        'B' => BASE_C | BASE_G | BASE_T,
+        'D' => BASE_A | BASE_G | BASE_T,
+        'H' => BASE_A | BASE_C | BASE_T,
+        'V' => BASE_A | BASE_C | BASE_G,
+        'N' => BASE_A | BASE_C | BASE_G | BASE_T,
+        '-' | '.' => 0,
+        _ => return None,
+    };
+    Some(mask)
+}
+
+/// Swaps A <-> T and C <-> G.
+pub fn complement_mask(mask: u8) -> u8 {
+    (mask & BASE_A) << 3 | (mask & BASE_C) << 1 | (mask & BASE_G) >> 1 | (mask & BASE_T) >> 3
+}
+
+/// A query position matches a base if every base the sequence letter may
+/// stand for is allowed by the query letter. Gaps never match.
+#[inline]
+pub fn is_match(query: u8, base: u8) -> bool {
+    base != 0 && base & !query == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complement_mask() {
+        assert_eq!(complement_mask(BASE_A), BASE_T);
+        assert_eq!(complement_mask(BASE_C), BASE_G);
+        assert_eq!(
+            complement_mask(base_mask('R').unwrap()),
+            base_mask('Y').unwrap()
+        );
+        assert_eq!(
+            complement_mask(base_mask('B').unwrap()),
+            base_mask('V').unwrap()
+        );
+        assert_eq!(
+            complement_mask(base_mask('N').unwrap()),
+            base_mask('N').unwrap()
+        );
+    }
+
+    #[test]
+    fn test_is_match() {
+        let n = base_mask('N').unwrap();
+        let r = base_mask('R').unwrap();
+
+        assert!(is_match(n, BASE_A));
+        assert!(is_match(r, BASE_G));
+        assert!(is_match(r, r));
+        assert!(!is_match(r, BASE_C));
+        assert!(!is_match(BASE_A, n));
+        assert!(!is_match(n, 0));
+    }
+}
@@ -0,0 +1,24 @@
+mod frame;
+mod fuzzy;
+mod iupac;
+mod motif;
+mod pattern;
+mod peptide;
+
+pub use self::fuzzy::DistanceMetric;
+pub use self::iupac::base_mask;
+pub use self::motif::Motif;
+pub use self::pattern::{Pattern, PatternTarget};
+pub use self::peptide::Peptide;
+
+use crate::error::EditorError;
+
+/// Number of positions scanned between two progress reports.
+const PROGRESS_INTERVAL: usize = 1 << 16;
+
+/// Parses a query with one of the `parse` functions of the query types.
+pub fn parse_query<T>(parse: impl Fn(&str) -> Option<T>, query: &str) -> Result<T, EditorError> {
+    parse(query).ok_or_else(|| EditorError::InvalidQuery {
+        query: query.to_string(),
+    })
+}
@@ -0,0 +1,238 @@
+use crate::{
+    packed::PackedNucleotides,
+    shared::{SearchHit, Strand},
+};
+
+use super::{
+    iupac::{base_mask, complement_mask, is_match},
+    PROGRESS_INTERVAL,
+};
+
+/// A nucleotide query which may contain IUPAC ambiguity codes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Motif {
+    forward: Vec<u8>,
+    reverse: Vec<u8>,
+}
+
+impl Motif {
+    /// Parses a query, ignoring whitespace and case.
+    ///
+    /// Returns `None` if the query is empty or contains gaps or non-IUPAC letters.
+    pub fn parse(query: &str) -> Option<Self> {
+        let forward = query
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| base_mask(c).filter(|&mask| mask != 0))
+            .collect::<Option<Vec<_>>>()?;
+        if forward.is_empty() {
+            return None;
+        }
+        let reverse = forward
+            .iter()
+            .rev()
+            .map(|&mask| complement_mask(mask))
+            .collect();
+        Some(Self { forward, reverse })
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.forward.len()
+    }
+
+    /// The patterns to search for on the forward strand, one per strand.
+    ///
+    /// The reverse complement is omitted for palindromic motifs.
+    pub(super) fn patterns(&self) -> Vec<(&[u8], Strand)> {
+        let mut patterns = vec![(self.forward.as_slice(), Strand::Forward)];
+        if self.reverse != self.forward {
+            patterns.push((self.reverse.as_slice(), Strand::Reverse));
+        }
+        patterns
+    }
+
+    /// Finds all occurrences on both strands, ordered by position.
+    ///
+    /// Palindromic motifs are only reported once, on the forward strand.
+    pub fn find_all(&self, sequence: &PackedNucleotides, circular: bool) -> Vec<SearchHit> {
+        self.find_all_with_progress(sequence, circular, &mut |_, _| true)
+            .unwrap_or_default()
+    }
+
+    /// Like [`Motif::find_all`], but calls `progress` with the number of scanned
+    /// and total positions of both strands every now and then. The search is
+    /// aborted, returning `None`, as soon as `progress` returns `false`.
+    pub fn find_all_with_progress(
+        &self,
+        sequence: &PackedNucleotides,
+        circular: bool,
+        progress: &mut dyn FnMut(usize, usize) -> bool,
+    ) -> Option<Vec<SearchHit>> {
+        let patterns = self.patterns();
+        let total = patterns.len() * sequence.len();
+        let mut hits = Vec::new();
+        for (round, (pattern, strand)) in patterns.into_iter().enumerate() {
+            let mut report = |done: usize| progress(round * sequence.len() + done, total);
+            for start in Self::find_pattern(pattern, sequence, circular, &mut report)? {
+                hits.push(self.hit(start, sequence.len(), strand));
+            }
+        }
+        hits.sort_by_key(|hit| (hit.start, hit.strand));
+        Some(hits)
+    }
+
+    fn hit(&self, start: usize, sequence_len: usize, strand: Strand) -> SearchHit {
+        let end = start + self.len();
+        SearchHit {
+            start,
+            end: if end > sequence_len {
+                end - sequence_len
+            } else {
+                end
+            },
+            strand,
+        }
+    }
+
+    fn find_pattern(
+        pattern: &[u8],
+        sequence: &PackedNucleotides,
+        circular: bool,
+        report: &mut dyn FnMut(usize) -> bool,
+    ) -> Option<Vec<usize>> {
+        let len = sequence.len();
+        if pattern.len() > len {
+            return Some(Vec::new());
+        }
+        let last_start = if circular {
+            len - 1
+        } else {
+            len - pattern.len()
+        };
+        let mut starts = Vec::new();
+        for start in 0..=last_start {
+            if start % PROGRESS_INTERVAL == 0 && !report(start) {
+                return None;
+            }
+            if pattern
+                .iter()
+                .enumerate()
+                .all(|(offset, &query)| is_match(query, sequence.mask((start + offset) % len)))
+            {
+                starts.push(start);
+            }
+        }
+        Some(starts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Motif;
+    use crate::shared::{SearchHit, Strand};
+    use crate::{packed::PackedNucleotides, search::iupac::base_mask};
+
+    fn masks(sequence: &str) -> PackedNucleotides {
+        let masks = sequence
+            .chars()
+            .map(|c| base_mask(c).unwrap())
+            .collect::<Vec<_>>();
+        PackedNucleotides::from_masks(&masks)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(Motif::parse("acg tnr").is_some());
+        assert_eq!(Motif::parse(""), None);
+        assert_eq!(Motif::parse("AC-T"), None);
+        assert_eq!(Motif::parse("ACXT"), None);
+    }
+
+    #[test]
+    fn test_find_forward_and_reverse() {
+        let motif = Motif::parse("ATGC").unwrap();
+        let hits = motif.find_all(&masks("ATGCAAGCAT"), false);
+        assert_eq!(
+            hits,
+            vec![
+                SearchHit {
+                    start: 0,
+                    end: 4,
+                    strand: Strand::Forward
+                },
+                SearchHit {
+                    start: 6,
+                    end: 10,
+                    strand: Strand::Reverse
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_ambiguous() {
+        let motif = Motif::parse("GRC").unwrap();
+        let hits = motif.find_all(&masks("GACTTGGCTT"), false);
+        assert_eq!(
+            hits,
+            vec![
+                SearchHit {
+                    start: 0,
+                    end: 3,
+                    strand: Strand::Forward
+                },
+                SearchHit {
+                    start: 5,
+                    end: 8,
+                    strand: Strand::Forward
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_palindrome_once() {
+        let motif = Motif::parse("GAATTC").unwrap();
+        let hits = motif.find_all(&masks("TTGAATTCTT"), false);
+        assert_eq!(
+            hits,
+            vec![SearchHit {
+                start: 2,
+                end: 8,
+                strand: Strand::Forward
+            }]
+        );
+    }
+
+    #[test]
+    fn test_find_across_origin() {
+        let motif = Motif::parse("TTAA").unwrap();
+        let sequence = masks("AACCCCCCTT");
+        assert_eq!(motif.find_all(&sequence, false), vec![]);
+        assert_eq!(
+            motif.find_all(&sequence, true),
+            vec![SearchHit {
+                start: 8,
+                end: 2,
+                strand: Strand::Forward
+            }]
+        );
+    }
+
+    #[test]
+    fn test_cancel() {
+        let motif = Motif::parse("GAATTC").unwrap();
+        let sequence = masks("AAGAATTCAA");
+        assert_eq!(
+            motif.find_all_with_progress(&sequence, false, &mut |_, _| false),
+            None
+        );
+        let mut reports = Vec::new();
+        motif.find_all_with_progress(&sequence, false, &mut |done, total| {
+            reports.push((done, total));
+            true
+        });
+        // Palindromes are searched on the forward strand only
+        assert_eq!(reports, [(0, 10)]);
+    }
+}
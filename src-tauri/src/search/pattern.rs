@@ -0,0 +1,200 @@
+use plasmid::traits::ToLetter;
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    packed::{decode, PackedNucleotides},
+    shared::{SearchHit, Strand},
+};
+
+use super::{frame::ReadingFrame, iupac::complement_mask, PROGRESS_INTERVAL};
+
+/// What a regular expression is matched against.
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternTarget {
+    /// The nucleotide letters of one strand, read 5' to 3'.
+    Nucleotides(Strand),
+    /// The one letter amino acid translation of a reading frame.
+    /// Untranslatable codons are represented by `X`.
+    Translation(ReadingFrame),
+}
+
+/// A case insensitive regular expression query.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    regex: Regex,
+}
+
+impl Pattern {
+    /// Returns `None` if the query is not a valid regular expression.
+    pub fn parse(query: &str) -> Option<Self> {
+        let regex = RegexBuilder::new(query)
+            .case_insensitive(true)
+            .build()
+            .ok()?;
+        Some(Self { regex })
+    }
+
+    /// Finds all non-overlapping, non-empty matches in a sequence, in
+    /// nucleotide coordinates.
+    pub fn find_all(&self, sequence: &PackedNucleotides, target: PatternTarget) -> Vec<SearchHit> {
+        self.find_all_with_progress(sequence, target, &mut |_, _| true)
+            .unwrap_or_default()
+    }
+
+    /// Like [`Pattern::find_all`], but calls `progress` with the number of
+    /// scanned and total letters of the target every now and then. The search
+    /// is aborted, returning `None`, as soon as `progress` returns `false`.
+    pub fn find_all_with_progress(
+        &self,
+        sequence: &PackedNucleotides,
+        target: PatternTarget,
+        progress: &mut dyn FnMut(usize, usize) -> bool,
+    ) -> Option<Vec<SearchHit>> {
+        let mut hits = match target {
+            PatternTarget::Nucleotides(strand) => {
+                let masks = sequence.masks(0..sequence.len());
+                let text: String = match strand {
+                    Strand::Forward => masks.map(|mask| decode(mask).to_letter()).collect(),
+                    Strand::Reverse => masks
+                        .rev()
+                        .map(|mask| decode(complement_mask(mask)).to_letter())
+                        .collect(),
+                };
+                self.find_ranges(&text, progress)?
+                    .into_iter()
+                    .map(|(start, end)| match strand {
+                        Strand::Forward => (start, end),
+                        Strand::Reverse => (sequence.len() - end, sequence.len() - start),
+                    })
+                    .map(|(start, end)| SearchHit { start, end, strand })
+                    .collect::<Vec<_>>()
+            }
+            PatternTarget::Translation(frame) => {
+                let text: String = frame
+                    .translate(sequence)
+                    .into_iter()
+                    .map(|amino_acid| amino_acid.unwrap_or('X'))
+                    .collect();
+                self.find_ranges(&text, progress)?
+                    .into_iter()
+                    .map(|(start, end)| frame.nucleotide_range(sequence.len(), start, end))
+                    .map(|(start, end)| SearchHit {
+                        start,
+                        end,
+                        strand: frame.strand,
+                    })
+                    .collect::<Vec<_>>()
+            }
+        };
+        hits.sort_by_key(|hit| hit.start);
+        Some(hits)
+    }
+
+    /// Letters in both targets are ASCII, so byte offsets are letter offsets.
+    ///
+    /// Progress can only be checked between two matches.
+    fn find_ranges(
+        &self,
+        text: &str,
+        progress: &mut dyn FnMut(usize, usize) -> bool,
+    ) -> Option<Vec<(usize, usize)>> {
+        if !progress(0, text.len()) {
+            return None;
+        }
+        let mut ranges = Vec::new();
+        let mut next_report = PROGRESS_INTERVAL;
+        for m in self.regex.find_iter(text) {
+            if m.end() >= next_report {
+                if !progress(m.end(), text.len()) {
+                    return None;
+                }
+                next_report = m.end() + PROGRESS_INTERVAL;
+            }
+            if !m.as_str().is_empty() {
+                ranges.push((m.start(), m.end()));
+            }
+        }
+        Some(ranges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pattern, PatternTarget};
+    use crate::packed::PackedNucleotides;
+    use crate::search::{frame::ReadingFrame, iupac::base_mask};
+    use crate::shared::{SearchHit, Strand};
+
+    fn masks(sequence: &str) -> PackedNucleotides {
+        let masks = sequence
+            .chars()
+            .map(|c| base_mask(c).unwrap())
+            .collect::<Vec<_>>();
+        PackedNucleotides::from_masks(&masks)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(Pattern::parse("ATG(...)+?(TAA|TAG|TGA)").is_some());
+        assert!(Pattern::parse("ATG(").is_none());
+    }
+
+    #[test]
+    fn test_find_nucleotides() {
+        let sequence = masks("CCATGAAACCCTAGCC");
+        let pattern = Pattern::parse("atg(...)*?(TAA|TAG|TGA)").unwrap();
+        assert_eq!(
+            pattern.find_all(&sequence, PatternTarget::Nucleotides(Strand::Forward)),
+            vec![SearchHit {
+                start: 2,
+                end: 14,
+                strand: Strand::Forward
+            }]
+        );
+
+        let pattern = Pattern::parse("GGG").unwrap();
+        assert_eq!(
+            pattern.find_all(&sequence, PatternTarget::Nucleotides(Strand::Reverse)),
+            vec![SearchHit {
+                start: 8,
+                end: 11,
+                strand: Strand::Reverse
+            }]
+        );
+    }
+
+    #[test]
+    fn test_find_translation() {
+        let sequence = masks("CCATGAAACCCTAGCC");
+        let frame = ReadingFrame {
+            strand: Strand::Forward,
+            offset: 2,
+        };
+        let pattern = Pattern::parse(r"MK.\*").unwrap();
+        assert_eq!(
+            pattern.find_all(&sequence, PatternTarget::Translation(frame)),
+            vec![SearchHit {
+                start: 2,
+                end: 14,
+                strand: Strand::Forward
+            }]
+        );
+    }
+
+    #[test]
+    fn test_cancel() {
+        let sequence = masks("CCATGAAACCCTAGCC");
+        let target = PatternTarget::Nucleotides(Strand::Forward);
+        let pattern = Pattern::parse("ATG").unwrap();
+        assert_eq!(
+            pattern.find_all_with_progress(&sequence, target, &mut |_, _| false),
+            None
+        );
+        assert_eq!(
+            pattern
+                .find_all_with_progress(&sequence, target, &mut |_, _| true)
+                .map(|hits| hits.len()),
+            Some(1)
+        );
+    }
+}
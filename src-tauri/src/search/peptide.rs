@@ -0,0 +1,174 @@
+use crate::{packed::PackedNucleotides, shared::SearchHit};
+
+use super::{frame::ReadingFrame, PROGRESS_INTERVAL};
+
+const AMINO_ACID_LETTERS: &str = "ACDEFGHIKLMNPQRSTVWY*";
+
+/// An amino acid query in one letter code.
+///
+/// `X` matches any amino acid, but not a stop codon. Stop codons are only
+/// matched by `*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Peptide {
+    residues: Vec<Option<char>>,
+}
+
+impl Peptide {
+    /// Parses a query, ignoring whitespace and case.
+    ///
+    /// Returns `None` if the query is empty or contains unknown letters.
+    pub fn parse(query: &str) -> Option<Self> {
+        let residues = query
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| match c.to_ascii_uppercase() {
+                'X' => Some(None),
+                c if AMINO_ACID_LETTERS.contains(c) => Some(Some(c)),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        if residues.is_empty() {
+            return None;
+        }
+        Some(Self { residues })
+    }
+
+    /// Finds all regions in any of the six reading frames that translate to
+    /// the peptide, ordered by position.
+    pub fn find_all(&self, sequence: &PackedNucleotides) -> Vec<SearchHit> {
+        self.find_all_with_progress(sequence, &mut |_, _| true)
+            .unwrap_or_default()
+    }
+
+    /// Like [`Peptide::find_all`], but calls `progress` with the number of
+    /// scanned and total nucleotides of all frames every now and then. The
+    /// search is aborted, returning `None`, as soon as `progress` returns `false`.
+    pub fn find_all_with_progress(
+        &self,
+        sequence: &PackedNucleotides,
+        progress: &mut dyn FnMut(usize, usize) -> bool,
+    ) -> Option<Vec<SearchHit>> {
+        let frames = ReadingFrame::all();
+        let total = frames.len() * sequence.len();
+        let mut hits = Vec::new();
+        for (round, frame) in frames.iter().enumerate() {
+            if !progress(round * sequence.len(), total) {
+                return None;
+            }
+            let translation = frame.translate(sequence);
+            if translation.len() < self.residues.len() {
+                continue;
+            }
+            for codon_start in 0..=translation.len() - self.residues.len() {
+                if codon_start > 0
+                    && codon_start % PROGRESS_INTERVAL == 0
+                    && !progress(round * sequence.len() + codon_start * 3, total)
+                {
+                    return None;
+                }
+                let is_match = self.residues.iter().zip(&translation[codon_start..]).all(
+                    |(residue, amino_acid)| match residue {
+                        Some(_) => residue == amino_acid,
+                        None => amino_acid.map_or(false, |amino_acid| amino_acid != '*'),
+                    },
+                );
+                if is_match {
+                    let (start, end) = frame.nucleotide_range(
+                        sequence.len(),
+                        codon_start,
+                        codon_start + self.residues.len(),
+                    );
+                    hits.push(SearchHit {
+                        start,
+                        end,
+                        strand: frame.strand,
+                    });
+                }
+            }
+        }
+        hits.sort_by_key(|hit| (hit.start, hit.strand));
+        Some(hits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Peptide;
+    use crate::packed::PackedNucleotides;
+    use crate::search::iupac::base_mask;
+    use crate::shared::{SearchHit, Strand};
+
+    fn masks(sequence: &str) -> PackedNucleotides {
+        let masks = sequence
+            .chars()
+            .map(|c| base_mask(c).unwrap())
+            .collect::<Vec<_>>();
+        PackedNucleotides::from_masks(&masks)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(Peptide::parse("hhh hhh").is_some());
+        assert!(Peptide::parse("MX*").is_some());
+        assert_eq!(Peptide::parse(""), None);
+        assert_eq!(Peptide::parse("MB"), None);
+    }
+
+    #[test]
+    fn test_find_forward() {
+        let sequence = masks("GATGCATCACCATCACCATCACTAA");
+        assert_eq!(
+            Peptide::parse("HHHHHH").unwrap().find_all(&sequence),
+            vec![SearchHit {
+                start: 4,
+                end: 22,
+                strand: Strand::Forward
+            }]
+        );
+        assert_eq!(
+            Peptide::parse("MXH").unwrap().find_all(&sequence),
+            vec![SearchHit {
+                start: 1,
+                end: 10,
+                strand: Strand::Forward
+            }]
+        );
+    }
+
+    #[test]
+    fn test_wildcard_skips_stop_codons() {
+        let sequence = masks("ATGTAACAC");
+        assert!(Peptide::parse("MXH")
+            .unwrap()
+            .find_all(&sequence)
+            .is_empty());
+        assert_eq!(Peptide::parse("M*H").unwrap().find_all(&sequence).len(), 1);
+    }
+
+    #[test]
+    fn test_find_reverse() {
+        // Reverse complement of ATG GAC TAC AAG GAC GAC GAT GAC AAG (M DYKDDDDK)
+        let sequence = masks("ACTTGTCATCGTCGTCCTTGTAGTCCATA");
+        assert_eq!(
+            Peptide::parse("DYKDDDDK").unwrap().find_all(&sequence),
+            vec![SearchHit {
+                start: 1,
+                end: 25,
+                strand: Strand::Reverse
+            }]
+        );
+    }
+
+    #[test]
+    fn test_cancel() {
+        let sequence = masks("ATGTAACAC");
+        let peptide = Peptide::parse("M").unwrap();
+        let mut reports = Vec::new();
+        let hits = peptide.find_all_with_progress(&sequence, &mut |done, total| {
+            reports.push((done, total));
+            done < 2 * sequence.len()
+        });
+        assert_eq!(hits, None);
+        assert_eq!(reports, [(0, 54), (9, 54), (18, 54)]);
+    }
+}
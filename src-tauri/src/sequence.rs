@@ -0,0 +1,386 @@
+use std::{fmt, ops::Range, sync::Arc};
+
+use parking_lot::Mutex;
+use plasmid::uni::IupacNucleotide;
+
+use crate::{
+    case_mask::{cased_letter, CaseMask},
+    packed::{encode, PackedNucleotides},
+};
+
+/// Preferred number of nucleotides per chunk.
+const CHUNK_SIZE: usize = 4096;
+
+/// Chunks are split once they grow beyond this size.
+const MAX_CHUNK_SIZE: usize = 2 * CHUNK_SIZE;
+
+/// Nucleotide storage for large sequences.
+///
+/// The nucleotides are kept in packed chunks of a few thousand, so that edits
+/// only repack the chunks they touch. Chunks are found by binary search over
+/// their start positions.
+#[derive(Clone, Default)]
+pub struct Sequence {
+    chunks: Vec<PackedNucleotides>,
+    /// Position of the first nucleotide of every chunk.
+    starts: Vec<usize>,
+    len: usize,
+    lowercase: CaseMask,
+    /// All nucleotides packed as one, built by the first search after an edit.
+    search_index: SearchIndex,
+}
+
+/// Cache of [`Sequence::search_index`], so that repeated searches don't join
+/// the chunks every time.
+#[derive(Default)]
+struct SearchIndex(Mutex<Option<Arc<PackedNucleotides>>>);
+
+impl Clone for SearchIndex {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(self.0.lock().clone()))
+    }
+}
+
+impl Sequence {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn from_nucleotides(nucleotides: &[IupacNucleotide], lowercase: &CaseMask) -> Self {
+        let mut sequence = Self::default();
+        sequence.insert(0, nucleotides, lowercase);
+        sequence
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = IupacNucleotide> + '_ {
+        self.range(0..self.len)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn range(
+        &self,
+        range: Range<usize>,
+    ) -> impl DoubleEndedIterator<Item = IupacNucleotide> + '_ {
+        self.chunk_ranges(range)
+            .flat_map(move |(index, range)| self.chunks[index].nucleotides(range))
+    }
+
+    /// All nucleotides packed as one, for searches which need random access.
+    ///
+    /// The chunks are joined on the first call after an edit and the result is
+    /// shared until the next one.
+    pub fn search_index(&self) -> Arc<PackedNucleotides> {
+        self.search_index
+            .0
+            .lock()
+            .get_or_insert_with(|| Arc::new(PackedNucleotides::join(&self.chunks)))
+            .clone()
+    }
+
+    /// A range of nucleotides, packed on their own.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn packed(&self, range: Range<usize>) -> PackedNucleotides {
+        let masks = self
+            .chunk_ranges(range)
+            .flat_map(|(index, range)| self.chunks[index].masks(range))
+            .collect::<Vec<_>>();
+        PackedNucleotides::from_masks(&masks)
+    }
+
+    #[cfg(test)]
+    pub fn to_vec(&self) -> Vec<IupacNucleotide> {
+        self.iter().collect()
+    }
+
+    pub fn is_lowercase(&self, position: usize) -> bool {
+        self.lowercase.is_lowercase(position)
+    }
+
+    /// Which nucleotides of a range are lowercase, relative to its start.
+    pub fn lowercase(&self, range: Range<usize>) -> CaseMask {
+        self.lowercase.slice(range)
+    }
+
+    /// The letters of a range, in the case they were entered in.
+    pub fn letters(&self, range: Range<usize>) -> String {
+        let start = range.start;
+        self.range(range)
+            .enumerate()
+            .map(|(offset, nucleotide)| {
+                cased_letter(&nucleotide, self.lowercase.is_lowercase(start + offset))
+            })
+            .collect()
+    }
+
+    /// Chunks overlapping with a range, with the overlapping part of each chunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    fn chunk_ranges(
+        &self,
+        range: Range<usize>,
+    ) -> impl DoubleEndedIterator<Item = (usize, Range<usize>)> + '_ {
+        assert!(
+            range.start <= range.end && range.end <= self.len,
+            "range {:?} out of bounds for a sequence of length {}",
+            range,
+            self.len
+        );
+        let chunks = if range.is_empty() {
+            0..0
+        } else {
+            self.chunk_index(range.start)..self.chunk_index(range.end - 1) + 1
+        };
+        chunks.map(move |index| {
+            let start = self.starts[index];
+            let from = range.start.saturating_sub(start);
+            let to = (range.end - start).min(self.chunks[index].len());
+            (index, from..to)
+        })
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `position` is out of bounds.
+    pub fn insert(
+        &mut self,
+        position: usize,
+        nucleotides: &[IupacNucleotide],
+        lowercase: &CaseMask,
+    ) {
+        let masks = nucleotides.iter().map(encode).collect::<Vec<_>>();
+        self.insert_masks(position, &masks, lowercase);
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `position` is out of bounds.
+    pub fn insert_packed(
+        &mut self,
+        position: usize,
+        nucleotides: &PackedNucleotides,
+        lowercase: &CaseMask,
+    ) {
+        let masks = nucleotides.masks(0..nucleotides.len()).collect::<Vec<_>>();
+        self.insert_masks(position, &masks, lowercase);
+    }
+
+    fn insert_masks(&mut self, position: usize, inserted: &[u8], lowercase: &CaseMask) {
+        assert!(position <= self.len, "position {} out of bounds", position);
+        if inserted.is_empty() {
+            return;
+        }
+        *self.search_index.0.get_mut() = None;
+        if self.chunks.is_empty() {
+            self.chunks.push(PackedNucleotides::default());
+            self.starts.push(0);
+        }
+
+        // Insertions at a chunk boundary go to the end of the previous chunk
+        let index = self.chunk_index(position.saturating_sub(1));
+        let offset = position - self.starts[index];
+        let chunk = &self.chunks[index];
+        let mut masks = Vec::with_capacity(chunk.len() + inserted.len());
+        masks.extend(chunk.masks(0..offset));
+        masks.extend_from_slice(inserted);
+        masks.extend(chunk.masks(offset..chunk.len()));
+        let pieces = if masks.len() > MAX_CHUNK_SIZE {
+            masks
+                .chunks(CHUNK_SIZE)
+                .map(PackedNucleotides::from_masks)
+                .collect()
+        } else {
+            vec![PackedNucleotides::from_masks(&masks)]
+        };
+        self.chunks.splice(index..=index, pieces);
+        self.len += inserted.len();
+        self.lowercase.insert(position, inserted.len(), lowercase);
+        self.update_starts(index);
+    }
+
+    /// Removes a range of nucleotides, returning them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn remove(&mut self, range: Range<usize>) -> Vec<IupacNucleotide> {
+        let removed = self.range(range.clone()).collect::<Vec<_>>();
+        if removed.is_empty() {
+            return removed;
+        }
+        *self.search_index.0.get_mut() = None;
+
+        let first = self.chunk_index(range.start);
+        self.lowercase.remove(range.clone());
+        for (index, range) in self.chunk_ranges(range).collect::<Vec<_>>() {
+            let chunk = &self.chunks[index];
+            let masks = chunk
+                .masks(0..range.start)
+                .chain(chunk.masks(range.end..chunk.len()))
+                .collect::<Vec<_>>();
+            self.chunks[index] = PackedNucleotides::from_masks(&masks);
+        }
+        self.chunks.retain(|chunk| !chunk.is_empty());
+
+        // Merge the chunks around the removed range if they became small
+        let index = first.min(self.chunks.len().saturating_sub(1));
+        if index + 1 < self.chunks.len()
+            && self.chunks[index].len() + self.chunks[index + 1].len() <= CHUNK_SIZE
+        {
+            let next = self.chunks.remove(index + 1);
+            let chunk = &self.chunks[index];
+            let masks = chunk
+                .masks(0..chunk.len())
+                .chain(next.masks(0..next.len()))
+                .collect::<Vec<_>>();
+            self.chunks[index] = PackedNucleotides::from_masks(&masks);
+        }
+
+        self.len -= removed.len();
+        self.update_starts(index);
+        removed
+    }
+
+    /// Index of the chunk containing `position`.
+    fn chunk_index(&self, position: usize) -> usize {
+        self.starts
+            .partition_point(|&start| start <= position)
+            .saturating_sub(1)
+    }
+
+    /// Recomputes the chunk starts from the chunk at `index` onward.
+    fn update_starts(&mut self, index: usize) {
+        self.starts.truncate(index.min(self.chunks.len()));
+        let mut start = match self.starts.last() {
+            Some(&last) => last + self.chunks[self.starts.len() - 1].len(),
+            None => 0,
+        };
+        for chunk in &self.chunks[self.starts.len()..] {
+            self.starts.push(start);
+            start += chunk.len();
+        }
+    }
+}
+
+impl FromIterator<IupacNucleotide> for Sequence {
+    fn from_iter<I: IntoIterator<Item = IupacNucleotide>>(iter: I) -> Self {
+        Self::from_nucleotides(&Vec::from_iter(iter), &CaseMask::default())
+    }
+}
+
+impl fmt::Debug for Sequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for Sequence {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.lowercase == other.lowercase && self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for Sequence {}
+
+impl<const N: usize> PartialEq<[IupacNucleotide; N]> for Sequence {
+    fn eq(&self, other: &[IupacNucleotide; N]) -> bool {
+        self.len == N && self.iter().eq(other.iter().cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use plasmid::{traits::TryFromLetter, uni::IupacNucleotide};
+
+    use super::{Sequence, CHUNK_SIZE};
+    use crate::case_mask::CaseMask;
+
+    fn nucleotides(letters: &str) -> Vec<IupacNucleotide> {
+        letters
+            .chars()
+            .map(|c| IupacNucleotide::try_from_letter(c).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut sequence = Sequence::default();
+        sequence.insert(0, &nucleotides("ACGT"), &CaseMask::default());
+        sequence.insert(2, &nucleotides("TT"), &CaseMask::default());
+        sequence.insert(6, &nucleotides("G"), &CaseMask::default());
+        assert_eq!(sequence.to_vec(), nucleotides("ACTTGTG"));
+
+        assert_eq!(sequence.remove(1..4), nucleotides("CTT"));
+        assert_eq!(sequence.to_vec(), nucleotides("AGTG"));
+        assert_eq!(
+            sequence.range(1..3).rev().collect::<Vec<_>>(),
+            nucleotides("TG")
+        );
+    }
+
+    #[test]
+    fn test_search_index() {
+        let mut sequence = Sequence::from_nucleotides(&nucleotides("ACGT"), &CaseMask::default());
+        let index = sequence.search_index();
+        assert_eq!(
+            index.masks(0..4).collect::<Vec<_>>(),
+            [0b0001, 0b0010, 0b0100, 0b1000]
+        );
+        assert!(Arc::ptr_eq(&index, &sequence.search_index()));
+
+        sequence.insert(4, &nucleotides("N"), &CaseMask::default());
+        assert_eq!(sequence.search_index().mask(4), 0b1111);
+        sequence.remove(0..2);
+        assert_eq!(sequence.search_index().to_vec(), nucleotides("GTN"));
+        assert_eq!(sequence.packed(1..3).to_vec(), nucleotides("TN"));
+    }
+
+    #[test]
+    fn test_many_chunks() {
+        // Mirror every edit on a Vec and compare
+        let pattern = nucleotides("ACGTTGCA");
+        let mut expected = Vec::new();
+        let mut sequence = Sequence::default();
+        for round in 0..40 {
+            let position = (round * 7919) % (expected.len() + 1);
+            let insert = pattern
+                .iter()
+                .cycle()
+                .take(CHUNK_SIZE / 2 + round * 97)
+                .cloned()
+                .collect::<Vec<_>>();
+            sequence.insert(position, &insert, &CaseMask::default());
+            expected.splice(position..position, insert);
+
+            let start = (round * 104_729) % expected.len();
+            let end = (start + round * 311).min(expected.len());
+            assert_eq!(sequence.remove(start..end), expected[start..end].to_vec());
+            expected.drain(start..end);
+
+            assert_eq!(sequence.len(), expected.len());
+            assert!(sequence.iter().eq(expected.iter().cloned()));
+            assert_eq!(sequence.search_index().to_vec(), expected);
+        }
+        assert!(sequence.chunks.len() > 1);
+        let middle = expected.len() / 2;
+        assert!(sequence
+            .range(middle - 10..middle + 10)
+            .eq(expected[middle - 10..middle + 10].iter().cloned()));
+    }
+}
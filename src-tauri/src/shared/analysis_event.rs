@@ -0,0 +1,28 @@
+use super::{ApproximateHit, SearchHit};
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum AnalysisResult {
+    Hits(Vec<SearchHit>),
+    ApproximateHits(Vec<ApproximateHit>),
+}
+
+/// Sent to the frontend while an analysis is running.
+#[derive(serde::Serialize, Clone)]
+pub struct AnalysisProgress {
+    pub task: usize,
+    pub document: String,
+    pub done: usize,
+    pub total: usize,
+}
+
+/// Sent to the frontend once an analysis is done.
+#[derive(serde::Serialize, Clone)]
+pub struct AnalysisFinished {
+    pub task: usize,
+    pub document: String,
+    /// `None` if the analysis was cancelled or failed.
+    pub result: Option<AnalysisResult>,
+    /// Whether the analysis crashed instead of finishing.
+    pub failed: bool,
+}
@@ -0,0 +1,18 @@
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointData {
+    pub id: usize,
+    pub name: String,
+    /// Milliseconds since the unix epoch.
+    pub created_at: u64,
+    pub bp_count: usize,
+}
+
+/// A region which differs between two sequences. `old_*` are positions in
+/// the checkpoint, `new_*` positions in the current sequence. Ends are exclusive.
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DiffHunk {
+    pub old_start: usize,
+    pub old_end: usize,
+    pub new_start: usize,
+    pub new_end: usize,
+}
@@ -0,0 +1,25 @@
+use crate::project::{Feature, Primer};
+
+use super::Strand;
+
+/// A copied fragment, together with where it came from.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ClipboardPayload {
+    /// The copied nucleotides as read on `strand`, which is also put on
+    /// the system clipboard as plain text.
+    pub text: String,
+    pub source_document: String,
+    pub start: usize,
+    pub end: usize,
+    pub strand: Strand,
+    pub is_cut: bool,
+    /// Features overlapping the copied range, clipped to it. Their positions
+    /// and strands are relative to `text`, and relative to the target
+    /// document once pasted.
+    #[serde(default)]
+    pub features: Vec<Feature>,
+    /// Primers overlapping the copied range, positioned like `features`.
+    /// Primers which only partly bind within the range have no position.
+    #[serde(default)]
+    pub primers: Vec<Primer>,
+}
@@ -22,18 +22,7 @@ impl DisplayCodon {
         }
 
         // Try to interpret the codon as a peptide
-        let peptide = if nucleotide_sequence.len() == 3 {
-            DnaCodon::try_from_str(
-                nucleotide_sequence
-                    .iter()
-                    .map(|n| n.to_letter())
-                    .collect::<String>(),
-            )
-            .map(|c| c.translate().to_letter())
-            .ok()
-        } else {
-            None
-        };
+        let peptide = translate_codon(nucleotide_sequence);
 
         DisplayCodon {
             nucleotides,
@@ -42,3 +31,20 @@ impl DisplayCodon {
         }
     }
 }
+
+/// Translates a codon into the letter of its amino acid.
+///
+/// Returns `None` for incomplete codons and codons containing ambiguous nucleotides.
+pub fn translate_codon(nucleotide_sequence: &[IupacNucleotide]) -> Option<char> {
+    if nucleotide_sequence.len() != 3 {
+        return None;
+    }
+    DnaCodon::try_from_str(
+        nucleotide_sequence
+            .iter()
+            .map(|n| n.to_letter())
+            .collect::<String>(),
+    )
+    .map(|c| c.translate().to_letter())
+    .ok()
+}
@@ -0,0 +1,29 @@
+use super::{CursorData, SelectionData};
+
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Inserted,
+    Deleted,
+}
+
+/// A range of nucleotides which was inserted or deleted.
+///
+/// Inserted ranges refer to the sequence right after the insertion, deleted
+/// ranges to the sequence right before the deletion.
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SequenceChange {
+    pub kind: ChangeKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Sent to the frontend after every command which changed a document.
+#[derive(serde::Serialize, Clone)]
+pub struct EditorChange {
+    pub document: String,
+    /// In the order they were applied.
+    pub changes: Vec<SequenceChange>,
+    pub bp_count: usize,
+    pub cursor: CursorData,
+    pub selection: Option<SelectionData>,
+}
@@ -0,0 +1,34 @@
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct HistoryStats {
+    pub entry_count: usize,
+    pub undo_count: usize,
+    pub redo_count: usize,
+    pub size_in_bytes: usize,
+    pub max_entries: usize,
+    pub max_bytes: usize,
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct HistoryNodeData {
+    pub id: usize,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub is_current: bool,
+    pub inserted: usize,
+    pub deleted: usize,
+    /// Milliseconds since the unix epoch.
+    pub created_at: u64,
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct HistoryBranchData {
+    /// The newest node of the branch.
+    pub tip: usize,
+    /// The node where the branch leaves the path to the current node.
+    pub fork: usize,
+    /// Number of entries from the root to the tip.
+    pub length: usize,
+    pub is_current: bool,
+    /// Milliseconds since the unix epoch.
+    pub created_at: u64,
+}
@@ -0,0 +1,10 @@
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct LibraryItem {
+    pub id: String,
+    pub name: Option<String>,
+    pub tags: Vec<String>,
+    pub feature_names: Vec<String>,
+    pub bp_count: usize,
+    /// Milliseconds since the unix epoch.
+    pub updated_at: Option<u64>,
+}
@@ -1,7 +1,23 @@
+mod analysis_event;
+mod checkpoint_data;
+mod clipboard_payload;
 mod display_codon;
+mod editor_change;
+mod history_stats;
+mod library_item;
+mod paste_options;
+mod search_hit;
 mod sequence_data;
 mod sequence_item;
 
-pub use self::display_codon::DisplayCodon;
-pub use self::sequence_data::{CursorData, SelectionData, SequenceData};
-pub use self::sequence_item::SequenceItem;
+pub use self::analysis_event::{AnalysisFinished, AnalysisProgress, AnalysisResult};
+pub use self::checkpoint_data::{CheckpointData, DiffHunk};
+pub use self::clipboard_payload::ClipboardPayload;
+pub use self::display_codon::{translate_codon, DisplayCodon};
+pub use self::editor_change::{ChangeKind, EditorChange, SequenceChange};
+pub use self::history_stats::{HistoryBranchData, HistoryNodeData, HistoryStats};
+pub use self::library_item::LibraryItem;
+pub use self::paste_options::{InvalidPolicy, PasteOptions, PasteResult, PasteSummary};
+pub use self::search_hit::{ApproximateHit, SearchHit, Strand};
+pub use self::sequence_data::{CursorData, SelectionData, SequenceData, SequenceWindowData};
+pub use self::sequence_item::{PackedSequenceItems, SequenceItem, SequenceItems};
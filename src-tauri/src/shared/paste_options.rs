@@ -0,0 +1,58 @@
+use super::ClipboardPayload;
+
+/// What to do with characters which aren't IUPAC nucleotide letters.
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidPolicy {
+    /// Fail without inserting anything.
+    Reject,
+    /// Leave the character out and count it in [`PasteSummary::skipped`].
+    Skip,
+    /// Insert an `N` instead.
+    ConvertToN,
+}
+
+impl Default for InvalidPolicy {
+    fn default() -> Self {
+        InvalidPolicy::Reject
+    }
+}
+
+/// How pasted text is cleaned up before it is inserted. Whitespace is always
+/// ignored; everything else is opt-in.
+#[derive(serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct PasteOptions {
+    /// Ignore digits, e.g. position numbers of formatted sequences.
+    pub strip_numbers: bool,
+    /// Ignore FASTA header and comment lines, which start with `>` or `;`.
+    pub strip_fasta_headers: bool,
+    /// Read `U` as `T`, for pasting RNA.
+    pub convert_rna: bool,
+    /// If the text contains a GenBank `ORIGIN` block, only paste the sequence
+    /// in it, without its position numbers.
+    pub genbank: bool,
+    pub invalid: InvalidPolicy,
+}
+
+/// What was changed while cleaning up pasted text.
+#[derive(serde::Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PasteSummary {
+    /// Number of inserted nucleotides.
+    pub inserted: usize,
+    /// Whether only the `ORIGIN` block of a GenBank record was pasted.
+    pub genbank_origin: bool,
+    pub stripped_headers: usize,
+    pub stripped_numbers: usize,
+    pub converted_rna: usize,
+    pub skipped: usize,
+    pub converted_to_n: usize,
+}
+
+/// What `clipboard_paste` inserted.
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PasteResult {
+    /// Set if the fragment was copied in the app, which is pasted as it was copied.
+    pub payload: Option<ClipboardPayload>,
+    /// Set if text from elsewhere was cleaned up with the paste options.
+    pub summary: Option<PasteSummary>,
+}
@@ -0,0 +1,27 @@
+#[derive(
+    serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+/// A search match in forward strand coordinates.
+///
+/// `end` is exclusive. For matches spanning the origin of a circular
+/// sequence, `end` wraps around and is smaller than or equal to `start`.
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    pub start: usize,
+    pub end: usize,
+    pub strand: Strand,
+}
+
+/// An approximate search match, see [`SearchHit`] for the coordinate convention.
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ApproximateHit {
+    pub start: usize,
+    pub end: usize,
+    pub strand: Strand,
+    pub distance: usize,
+}
@@ -1,20 +1,31 @@
-use super::SequenceItem;
+use super::SequenceItems;
 
 #[derive(serde::Serialize)]
 pub struct SequenceData {
-    pub sequence: Option<Vec<SequenceItem>>,
+    pub sequence: Option<SequenceItems>,
     pub bp_count: usize,
     pub cursor: CursorData,
     pub selection: Option<SelectionData>,
 }
 
+/// The sequence items of a window of the sequence.
 #[derive(serde::Serialize)]
+pub struct SequenceWindowData {
+    pub items: SequenceItems,
+    /// Index of the first nucleotide of the first item.
+    pub start_index: usize,
+    pub bp_count: usize,
+    pub cursor: CursorData,
+    pub selection: Option<SelectionData>,
+}
+
+#[derive(serde::Serialize, Clone)]
 pub struct CursorData {
     pub position: usize,
     pub is_at_end: bool,
 }
 
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, Clone)]
 pub struct SelectionData {
     pub start: usize,
     pub end: usize,
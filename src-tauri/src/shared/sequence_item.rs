@@ -5,3 +5,27 @@ pub struct SequenceItem {
     pub peptide: Option<char>,
     pub start_index: usize,
 }
+
+/// Sequence items in a compact form, which is much smaller to transfer than a
+/// list of [`SequenceItem`]s.
+///
+/// Every item but the last one spans three nucleotides, so the codon of item `i`
+/// is `codons[3 * i..3 * i + 3]` and it starts at `start_index + 3 * i`.
+#[derive(serde::Serialize, Debug, Default, PartialEq, Eq)]
+pub struct PackedSequenceItems {
+    /// Index of the first nucleotide of the first item.
+    pub start_index: usize,
+    /// Letters of all codons.
+    pub codons: String,
+    /// Letters of all anticodons, in the same order as `codons`.
+    pub anticodons: String,
+    /// One letter per item, a space for items without a peptide.
+    pub peptides: String,
+}
+
+#[derive(serde::Serialize)]
+#[serde(untagged)]
+pub enum SequenceItems {
+    Items(Vec<SequenceItem>),
+    Packed(PackedSequenceItems),
+}
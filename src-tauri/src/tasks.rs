@@ -0,0 +1,295 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use parking_lot::Mutex;
+
+use crate::documents::DocumentId;
+
+pub type TaskId = usize;
+
+/// Number of tasks running at the same time. Further tasks wait in a queue.
+const MAX_WORKERS: usize = 4;
+
+/// Handed to a running task to report its progress and to notice cancellation.
+pub struct TaskContext {
+    id: TaskId,
+    cancelled: Arc<AtomicBool>,
+    progress: Box<dyn Fn(TaskId, usize, usize) + Send>,
+}
+
+impl TaskContext {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Reports that `done` out of `total` units of work are finished.
+    ///
+    /// Returns `false` if the task was cancelled and should stop.
+    pub fn report(&self, done: usize, total: usize) -> bool {
+        if self.is_cancelled() {
+            return false;
+        }
+        (self.progress)(self.id, done, total);
+        true
+    }
+}
+
+/// How a task ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskOutcome<R> {
+    Done(R),
+    Cancelled,
+    /// The task panicked.
+    Failed,
+}
+
+struct RunningTask {
+    document: DocumentId,
+    cancelled: Arc<AtomicBool>,
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+struct Queue {
+    jobs: VecDeque<Job>,
+    workers: usize,
+}
+
+/// Runs long analyses on a few background threads, so that they don't block editing.
+///
+/// Every task belongs to a document and should be cancelled when the
+/// sequence of the document changes, see [`Tasks::cancel_document`].
+#[derive(Default)]
+pub struct Tasks {
+    next_id: AtomicUsize,
+    /// Queued and running tasks.
+    running: Arc<Mutex<HashMap<TaskId, RunningTask>>>,
+    queue: Arc<Mutex<Queue>>,
+}
+
+impl Tasks {
+    /// Runs `task` on a worker thread and passes how it ended to `finish`.
+    /// Tasks cancelled while queued don't run.
+    pub fn spawn<R: 'static>(
+        &self,
+        document: DocumentId,
+        progress: impl Fn(TaskId, usize, usize) + Send + 'static,
+        task: impl FnOnce(&TaskContext) -> R + Send + 'static,
+        finish: impl FnOnce(TaskId, TaskOutcome<R>) + Send + 'static,
+    ) -> TaskId {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.running.lock().insert(
+            id,
+            RunningTask {
+                document,
+                cancelled: cancelled.clone(),
+            },
+        );
+
+        let running = self.running.clone();
+        let job = Box::new(move || {
+            let context = TaskContext {
+                id,
+                cancelled,
+                progress: Box::new(progress),
+            };
+            let outcome = if context.is_cancelled() {
+                TaskOutcome::Cancelled
+            } else {
+                match panic::catch_unwind(AssertUnwindSafe(|| task(&context))) {
+                    Ok(_) if context.is_cancelled() => TaskOutcome::Cancelled,
+                    Ok(result) => TaskOutcome::Done(result),
+                    Err(_) => TaskOutcome::Failed,
+                }
+            };
+            running.lock().remove(&id);
+            finish(id, outcome);
+        });
+
+        let mut queue = self.queue.lock();
+        queue.jobs.push_back(job);
+        if queue.workers < MAX_WORKERS {
+            queue.workers += 1;
+            let queue = self.queue.clone();
+            thread::spawn(move || work(&queue));
+        }
+        id
+    }
+
+    /// Returns `false` if the task isn't running.
+    pub fn cancel(&self, id: TaskId) -> bool {
+        match self.running.lock().get(&id) {
+            Some(task) => {
+                task.cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancels all tasks of a document.
+    pub fn cancel_document(&self, document: &str) {
+        for task in self.running.lock().values() {
+            if task.document == document {
+                task.cancelled.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Ids of the running tasks of a document.
+    pub fn running(&self, document: &str) -> Vec<TaskId> {
+        let mut ids = self
+            .running
+            .lock()
+            .iter()
+            .filter(|(_, task)| task.document == document)
+            .map(|(&id, _)| id)
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+/// Runs queued jobs until the queue is empty.
+fn work(queue: &Mutex<Queue>) {
+    loop {
+        let job = {
+            let mut queue = queue.lock();
+            match queue.jobs.pop_front() {
+                Some(job) => job,
+                None => {
+                    queue.workers -= 1;
+                    return;
+                }
+            }
+        };
+        // A panicking `finish` mustn't take the worker's slot with it
+        let _ = panic::catch_unwind(AssertUnwindSafe(job));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread, time::Duration};
+
+    use super::{TaskOutcome, Tasks, MAX_WORKERS};
+
+    #[test]
+    fn test_finish_and_cancel() {
+        let tasks = Tasks::default();
+        let (sender, receiver) = mpsc::channel();
+
+        let finished = sender.clone();
+        let id = tasks.spawn(
+            "vector".to_string(),
+            |_, _, _| {},
+            |_| 42,
+            move |id, result| finished.send((id, result)).unwrap(),
+        );
+        assert_eq!(receiver.recv().unwrap(), (id, TaskOutcome::Done(42)));
+
+        // Runs until it is cancelled
+        let (started, wait) = mpsc::channel();
+        let id = tasks.spawn(
+            "vector".to_string(),
+            |_, _, _| {},
+            move |context| {
+                started.send(()).unwrap();
+                let mut done = 0;
+                while context.report(done, usize::MAX) {
+                    done += 1;
+                }
+                done
+            },
+            move |id, result| sender.send((id, result)).unwrap(),
+        );
+        wait.recv().unwrap();
+        assert_eq!(tasks.running("vector"), [id]);
+        assert!(tasks.running("insert").is_empty());
+        tasks.cancel_document("insert");
+        tasks.cancel_document("vector");
+        assert_eq!(receiver.recv().unwrap(), (id, TaskOutcome::Cancelled));
+        assert!(tasks.running("vector").is_empty());
+        assert!(!tasks.cancel(id));
+    }
+
+    #[test]
+    fn test_tasks_beyond_the_workers_wait() {
+        let tasks = Tasks::default();
+        let (started, wait) = mpsc::channel();
+        let (sender, receiver) = mpsc::channel();
+        let ids = (0..MAX_WORKERS + 1)
+            .map(|_| {
+                let started = started.clone();
+                let finished = sender.clone();
+                tasks.spawn(
+                    "vector".to_string(),
+                    |_, _, _| {},
+                    move |context| {
+                        started.send(()).unwrap();
+                        while context.report(0, 1) {
+                            thread::yield_now();
+                        }
+                    },
+                    move |id, result| finished.send((id, result)).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for _ in 0..MAX_WORKERS {
+            wait.recv().unwrap();
+        }
+        assert!(wait.recv_timeout(Duration::from_millis(50)).is_err());
+        assert_eq!(tasks.running("vector"), ids);
+
+        // The queued task is dropped without running
+        tasks.cancel_document("vector");
+        let mut finished = (0..ids.len())
+            .map(|_| receiver.recv().unwrap())
+            .collect::<Vec<_>>();
+        finished.sort_unstable_by_key(|&(id, _)| id);
+        assert_eq!(
+            finished,
+            ids.iter()
+                .map(|&id| (id, TaskOutcome::Cancelled))
+                .collect::<Vec<_>>()
+        );
+        assert!(wait.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_panics_free_the_worker() {
+        let tasks = Tasks::default();
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..MAX_WORKERS + 1 {
+            let finished = sender.clone();
+            tasks.spawn(
+                "vector".to_string(),
+                |_, _, _| {},
+                |_| -> usize { panic!("analysis failed") },
+                move |id, outcome| finished.send((id, outcome)).unwrap(),
+            );
+        }
+        for _ in 0..MAX_WORKERS + 1 {
+            assert_eq!(receiver.recv().unwrap().1, TaskOutcome::Failed);
+        }
+        assert!(tasks.running("vector").is_empty());
+
+        let id = tasks.spawn(
+            "vector".to_string(),
+            |_, _, _| {},
+            |_| 42,
+            move |id, outcome| sender.send((id, outcome)).unwrap(),
+        );
+        assert_eq!(receiver.recv().unwrap(), (id, TaskOutcome::Done(42)));
+    }
+}
@@ -1,9 +1,11 @@
 import { useEffect, useState } from 'react'
+import { invoke } from '@tauri-apps/api/tauri'
 import { useRecoilState } from 'recoil'
 import styled from 'styled-components'
 
 import Modal from './Modal'
 import ModalButton from './ModalButton'
+import { activeProjectIdState } from '../../state/atoms'
 import { projectSelector } from '../../state/selectors'
 
 type Props = {
@@ -15,6 +17,7 @@ type Props = {
 
 const EditProjectModal = ({ className, isOpen, onClose, projectId }: Props) => {
     const [project, setProject] = useRecoilState(projectSelector(projectId))
+    const [activeProjectId, setActiveProjectId] = useRecoilState(activeProjectIdState)
     const [name, setName] = useState(project?.name ?? '')
 
     useEffect(() => {
@@ -35,7 +38,10 @@ const EditProjectModal = ({ className, isOpen, onClose, projectId }: Props) => {
         }
     }
 
-    const handleDeleteProject = () => {
+    const handleDeleteProject = async () => {
+        if (activeProjectId === projectId) setActiveProjectId(null)
+        // Releases the editor of the project and deletes its stored history
+        await invoke('delete_document', { document: projectId })
         setProject(null)
         handleClose()
     }
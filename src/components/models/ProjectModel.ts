@@ -4,6 +4,7 @@ interface ProjectModelParams {
     id: string,
     name?: string,
     sequence?: string,
+    history?: unknown,
     createdAt?: Date,
     updatedAt?: Date
 }
@@ -13,6 +14,7 @@ interface ProjectModelParamsV1 {
     id: string,
     name: string,
     sequence: string,
+    history?: unknown,
     createdAt: Date,
     updatedAt?: Date
 }
@@ -22,6 +24,7 @@ export default class ProjectModel {
     id: string
     name?: string
     sequence: string
+    history?: unknown
     createdAt: Date
     updatedAt?: Date
 
@@ -30,6 +33,7 @@ export default class ProjectModel {
             id: uuid(),
             name: undefined,
             sequence: '',
+            history: undefined,
             createdAt: new Date(),
             updatedAt: undefined,
         }
@@ -41,6 +45,7 @@ export default class ProjectModel {
         this.id = mergedParams.id
         this.name = mergedParams.name
         this.sequence = mergedParams.sequence
+        this.history = mergedParams.history
         this.createdAt = mergedParams.createdAt
         this.updatedAt = mergedParams.updatedAt
     }
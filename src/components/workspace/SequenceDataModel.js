@@ -93,10 +93,28 @@ export class SequenceDataItemModel {
     }
 }
 
+/**
+ * Expands the packed sequence items sent by the backend into one item per codon.
+ */
+export const unpackSequenceItems = (packed) => {
+    const items = []
+    for (let index = 0; index < packed.peptides.length; index++) {
+        const peptide = packed.peptides[index]
+        items.push({
+            codon: [...packed.codons.slice(index * 3, index * 3 + 3)],
+            anticodon: [...packed.anticodons.slice(index * 3, index * 3 + 3)],
+            peptide: peptide === ' ' ? null : peptide,
+            start_index: packed.start_index + index * 3,
+        })
+    }
+    return items
+}
+
 export default class SequenceDataModel {
     constructor(data) {
+        const sequence = data?.sequence ?? []
         const patchedData = {
-            sequence: data?.sequence ?? [],
+            sequence: Array.isArray(sequence) ? sequence : unpackSequenceItems(sequence),
             bp_count: data?.bp_count ?? 0,
         }
         this._data = patchedData
@@ -104,6 +122,16 @@ export default class SequenceDataModel {
         this._selection = new SequenceDataSelectionModel(this._data.selection)
     }
 
+    /**
+     * Replaces every item from the start of the window on with the items of the window.
+     * Edits shift the reading frame, so a window always reaches to the end of the sequence.
+     */
+    withWindow(window) {
+        const items = Array.isArray(window.items) ? window.items : unpackSequenceItems(window.items)
+        const kept = this._data.sequence.filter(item => item.start_index < window.start_index)
+        return new SequenceDataModel({ sequence: [...kept, ...items], bp_count: window.bp_count })
+    }
+
     /**
      * @returns {number}
      */
@@ -24,6 +24,8 @@ const Editor = ({ className }: Props) => {
         cursor,
         sequence,
         selection,
+        notice,
+        dismissNotice,
         handlers: {
             handleKeyDown,
             handleMouseEvent,
@@ -69,6 +71,11 @@ const Editor = ({ className }: Props) => {
                     >
                         <RendererComponent sequence={sequence} cursor={cursor} selection={selection} showCursor={renderCursor} />
                     </div>
+                    {notice && (
+                        <div className={`notice notice--${notice.kind}`} role="status" onClick={dismissNotice}>
+                            {notice.text}
+                        </div>
+                    )}
                 </>
             )}
         </div>
@@ -93,6 +100,25 @@ export default styled(Editor)`
         }
     }
 
+    .notice {
+        flex-shrink: 0;
+        padding: .5rem 1rem;
+        border-top: 1px solid hsl(0,0%,75%);
+        font-size: 10pt;
+        cursor: pointer;
+        user-select: none;
+
+        &--error {
+            background: hsl(0,75%,95%);
+            color: hsl(0,60%,35%);
+        }
+
+        &--info {
+            background: hsl(210,60%,96%);
+            color: hsl(210,40%,30%);
+        }
+    }
+
     .no-project {
         user-select: none;
         display: flex;
@@ -1,7 +1,7 @@
 import styled from 'styled-components'
 import { useRecoilState } from 'recoil'
 
-import { editorHintState, editorRendererState } from '../../../state/atoms'
+import { editorHintState, editorRendererState, pasteOptionsState, InvalidPolicy } from '../../../state/atoms'
 
 import { rendererList } from '../renderers'
 import MenuButton from '../../MenuButton'
@@ -14,6 +14,7 @@ type Props = {
 const EditorToolbar = ({ className }: Props) => {
     const [editorHints, setEditorHints] = useRecoilState(editorHintState)
     const [renderer, setRenderer] = useRecoilState(editorRendererState)
+    const [pasteOptions, setPasteOptions] = useRecoilState(pasteOptionsState)
 
     return (
         <Toolbar className={className}>
@@ -56,6 +57,56 @@ const EditorToolbar = ({ className }: Props) => {
                         <label htmlFor="highlight-active-codon">Highlight Active Codon</label>
                     </div>
                 </MenuButton>
+                <MenuButton title="Paste">
+                    <div className="input_wrapper">
+                        <input
+                            type="checkbox"
+                            id="paste-strip-numbers"
+                            checked={pasteOptions.stripNumbers}
+                            onChange={e => setPasteOptions(options => ({...options, stripNumbers: e.target.checked}))}
+                        />
+                        <label htmlFor="paste-strip-numbers">Ignore Numbers</label>
+                    </div>
+                    <div className="input_wrapper">
+                        <input
+                            type="checkbox"
+                            id="paste-strip-fasta-headers"
+                            checked={pasteOptions.stripFastaHeaders}
+                            onChange={e => setPasteOptions(options => ({...options, stripFastaHeaders: e.target.checked}))}
+                        />
+                        <label htmlFor="paste-strip-fasta-headers">Ignore FASTA Headers</label>
+                    </div>
+                    <div className="input_wrapper">
+                        <input
+                            type="checkbox"
+                            id="paste-convert-rna"
+                            checked={pasteOptions.convertRna}
+                            onChange={e => setPasteOptions(options => ({...options, convertRna: e.target.checked}))}
+                        />
+                        <label htmlFor="paste-convert-rna">Read U as T</label>
+                    </div>
+                    <div className="input_wrapper">
+                        <input
+                            type="checkbox"
+                            id="paste-genbank"
+                            checked={pasteOptions.genbank}
+                            onChange={e => setPasteOptions(options => ({...options, genbank: e.target.checked}))}
+                        />
+                        <label htmlFor="paste-genbank">Only GenBank ORIGIN</label>
+                    </div>
+                    <div className="input_wrapper">
+                        <label htmlFor="paste-invalid">Other Characters</label>
+                        <select
+                            id="paste-invalid"
+                            value={pasteOptions.invalid}
+                            onChange={e => setPasteOptions(options => ({...options, invalid: e.target.value as InvalidPolicy}))}
+                        >
+                            <option value="Reject">Cancel Paste</option>
+                            <option value="Skip">Skip</option>
+                            <option value="ConvertToN">Insert N</option>
+                        </select>
+                    </div>
+                </MenuButton>
             </div>
             <div className="input_container" style={{flexGrow: 1}}></div>
             { rendererList.length > 1 && (
@@ -1,5 +1,6 @@
-import { FilePlus, Edit } from 'react-feather'
+import { FilePlus, Edit, X } from 'react-feather'
 import { useState } from 'react'
+import { invoke } from '@tauri-apps/api/tauri'
 import { useRecoilState, useRecoilValue } from 'recoil'
 import styled from 'styled-components'
 
@@ -43,6 +44,15 @@ const Sidebar = ({ className }: Props) => {
         setShowEditProjectModal(false)
     }
 
+    const closeProject = async (e: React.MouseEvent, id: string) => {
+        e.preventDefault()
+        e.stopPropagation()
+
+        setActiveProjectId(null)
+        // Releases the editor of the project in the backend
+        await invoke('close_document', { document: id })
+    }
+
     return (
         <div className={className}>
             <NewProjectModal isOpen={showNewProjectModal} onClose={closeNewProjectModal} />
@@ -70,6 +80,11 @@ const Sidebar = ({ className }: Props) => {
                             <div className="icon" onClick={e => openEditProjectModal(e, project.id)}>
                                 <Edit size={16} />
                             </div>
+                            {project.id === activeProjectId && (
+                                <div className="icon" title="Close" onClick={e => closeProject(e, project.id)}>
+                                    <X size={16} />
+                                </div>
+                            )}
                         </div>
                         <div className="row">
                             {!project.updatedAt && (
@@ -1,38 +1,112 @@
-import React, { useState, useEffect, useCallback } from 'react'
+import React, { useState, useEffect, useCallback, useMemo, useRef } from 'react'
 import { invoke } from '@tauri-apps/api/tauri'
-import { useRecoilState } from 'recoil'
+import { listen } from '@tauri-apps/api/event'
+import { useRecoilState, useRecoilValue } from 'recoil'
 
+import { pasteOptionsState, PasteOptions } from '../../state/atoms'
 import { activeProjectSelector } from '../../state/selectors'
+import { describeEditorError } from '../../util/editorError'
 
 import SequenceDataModel, { SequenceDataCursorModel, SequenceDataSelectionModel } from './SequenceDataModel'
 import useSelection from './useSelection'
 
-const Bridge = {
-    calculateSequenceData: (force: boolean) => invoke('calculate_sequence_data', { force }),
-    insert: (letter: string) => invoke('sequence_insert', { letter }),
-    insertAll: (text: string) => invoke('sequence_insert_all', { text }),
-    delete: () => invoke('sequence_delete'),
-    deleteNext: () => invoke('sequence_delete_next'),
-    moveCursorTo: (index: number) => invoke('move_cursor', { index }),
-    moveCursorLeft: () => invoke('move_cursor_left'),
-    moveCursorRight: () => invoke('move_cursor_right'),
-    moveCursorToCodonStart: () => invoke('move_cursor_to_codon_start'),
-    moveCursorToCodonEnd: () => invoke('move_cursor_to_codon_end'),
-    moveCursorToStart: () => invoke('move_cursor_to_start'),
-    moveCursorToEnd: () => invoke('move_cursor_to_end'),
-    setSelection: (start: number, end: number) => invoke('set_selection', { start, end }),
-    selectAll: () => invoke('set_selection_all'),
-    resetSelection: () => invoke('reset_selection'),
-    expandSelectionLeft: () => invoke('expand_selection_left'),
-    expandSelectionRight: () => invoke('expand_selection_right'),
-    getSelectedSequence: () => invoke('get_selected_sequence') as Promise<string>,
-    undo: () => invoke('undo'),
-    redo: () => invoke('redo'),
-    initializeEditor: (sequence: string) => invoke('initialize_editor', { sequence }),
-}
+const createBridge = (document: string) => ({
+    calculateSequenceWindow: (start: number, end: number) => invoke('calculate_sequence_window', { document, start, end, packed: true }) as Promise<SequenceWindow>,
+    insert: (letter: string) => invoke('sequence_insert', { document, letter }),
+    insertAll: (text: string, options?: Record<string, unknown>) => invoke('sequence_insert_all', { document, text, options }),
+    delete: () => invoke('sequence_delete', { document }),
+    deleteNext: () => invoke('sequence_delete_next', { document }),
+    moveCursorTo: (index: number) => invoke('move_cursor', { document, index }),
+    moveCursorLeft: () => invoke('move_cursor_left', { document }),
+    moveCursorRight: () => invoke('move_cursor_right', { document }),
+    moveCursorToCodonStart: () => invoke('move_cursor_to_codon_start', { document }),
+    moveCursorToCodonEnd: () => invoke('move_cursor_to_codon_end', { document }),
+    moveCursorToStart: () => invoke('move_cursor_to_start', { document }),
+    moveCursorToEnd: () => invoke('move_cursor_to_end', { document }),
+    setSelection: (start: number, end: number) => invoke('set_selection', { document, start, end }),
+    selectAll: () => invoke('set_selection_all', { document }),
+    resetSelection: () => invoke('reset_selection', { document }),
+    expandSelectionLeft: () => invoke('expand_selection_left', { document }),
+    expandSelectionRight: () => invoke('expand_selection_right', { document }),
+    getSelectedSequence: () => invoke('get_selected_sequence', { document }) as Promise<string>,
+    copy: () => invoke('clipboard_copy', { document, strand: 'Forward' }),
+    cut: () => invoke('clipboard_cut', { document, strand: 'Forward' }),
+    paste: (options: PasteOptions) => invoke('clipboard_paste', {
+        document,
+        options: {
+            strip_numbers: options.stripNumbers,
+            strip_fasta_headers: options.stripFastaHeaders,
+            convert_rna: options.convertRna,
+            genbank: options.genbank,
+            invalid: options.invalid,
+        },
+    }) as Promise<PasteResult>,
+    undo: () => invoke('undo', { document }),
+    redo: () => invoke('redo', { document }),
+    initializeEditor: (sequence: string, history?: unknown) => invoke('initialize_editor', { document, sequence, history }) as Promise<boolean>,
+    storeProject: () => invoke('store_project', { document }),
+    setProjectInfo: (info: Record<string, unknown>) => invoke('set_project_info', { document, info }),
+})
 
 const iupacChars = "ACGTWSMKRYBVDHN-"
 
+type PasteSummary = {
+    inserted: number,
+    genbank_origin: boolean,
+    stripped_headers: number,
+    stripped_numbers: number,
+    converted_rna: number,
+    skipped: number,
+    converted_to_n: number,
+}
+
+type PasteResult = {
+    payload: Record<string, unknown> | null,
+    summary: PasteSummary | null,
+}
+
+/**
+ * Describes how pasted text was cleaned up, or returns `null` if it was pasted as is.
+ */
+const describePasteSummary = (summary: PasteSummary) => {
+    const changes = [
+        summary.genbank_origin && 'only pasted the GenBank ORIGIN',
+        summary.stripped_headers > 0 && `ignored ${summary.stripped_headers} FASTA header line(s)`,
+        summary.stripped_numbers > 0 && `ignored ${summary.stripped_numbers} digit(s)`,
+        summary.converted_rna > 0 && `read ${summary.converted_rna} U as T`,
+        summary.skipped > 0 && `skipped ${summary.skipped} invalid character(s)`,
+        summary.converted_to_n > 0 && `inserted N for ${summary.converted_to_n} invalid character(s)`,
+    ].filter(Boolean)
+    if (changes.length === 0) return null
+    return `Pasted ${summary.inserted} bp, ${changes.join(', ')}`
+}
+
+// TODO: Fix type annotations!
+type SequenceWindow = {
+    start_index: number,
+    items: unknown,
+    bp_count: number,
+    cursor: Record<string, unknown>,
+    selection: Record<string, unknown> | null,
+}
+
+/** Sent by the backend after every command which changed a document. */
+type EditorChange = {
+    document: string,
+    changes: { kind: 'Inserted' | 'Deleted', start: number, end: number }[],
+    bp_count: number,
+    cursor: Record<string, unknown>,
+    selection: Record<string, unknown> | null,
+}
+
+/** How long a notice is shown, in milliseconds. */
+const NOTICE_DURATION = 4000
+
+export type EditorNotice = {
+    kind: 'error' | 'info',
+    text: string,
+}
+
 const findIndex = (currentTarget: HTMLElement) => {
     if (currentTarget.dataset.index) return parseInt(currentTarget.dataset.index)
     if (currentTarget.parentElement?.dataset.index) return parseInt(currentTarget.parentElement.dataset.index)
@@ -44,6 +118,8 @@ type useEditorReturnTypes = {
     cursor: import('./SequenceDataModel').SequenceDataCursorModel,
     sequence: import('./SequenceDataModel').default,
     selection: import('./SequenceDataModel').SequenceDataSelectionModel,
+    notice: EditorNotice | null,
+    dismissNotice: () => void,
     handlers: {
         handleKeyDown: (e: React.KeyboardEvent) => void,
         handleMouseEvent: (e: React.MouseEvent) => void,
@@ -55,8 +131,23 @@ const useEditor = (): useEditorReturnTypes => {
     const [sequenceModel, setSequenceModel] = useState(new SequenceDataModel())
     const [cursorModel, setCursorModel] = useState(new SequenceDataCursorModel())
     const [selectionModel, setSelectionModel] = useState(new SequenceDataSelectionModel())
+    const [notice, setNotice] = useState<EditorNotice | null>(null)
+
+    const showError = useCallback((error: unknown) => {
+        setNotice({ kind: 'error', text: describeEditorError(error) })
+    }, [])
+
+    const dismissNotice = useCallback(() => setNotice(null), [])
+
+    useEffect(() => {
+        if (!notice) return
+        const timeout = setTimeout(() => setNotice(null), NOTICE_DURATION)
+        return () => clearTimeout(timeout)
+    }, [notice])
 
     const [activeProject, setActiveProject] = useRecoilState(activeProjectSelector)
+    const pasteOptions = useRecoilValue(pasteOptionsState)
+    const Bridge = useMemo(() => createBridge(activeProject?.id ?? ''), [activeProject?.id])
 
     const {
         isSelecting,
@@ -67,10 +158,19 @@ const useEditor = (): useEditorReturnTypes => {
     } = useSelection()
 
     useEffect(() => {
+        if (!activeProject?.id) return
         const initialize = async () => {
             setIsLoading(true)
-            await Bridge.initializeEditor(activeProject?.sequence ?? '')
-            await updateSequence(true)
+            try {
+                // Projects saved before the history moved to the app data directory still carry it
+                const historyRestored = await Bridge.initializeEditor(activeProject?.sequence ?? '', activeProject?.history)
+                await updateSequence(0)
+                if (!historyRestored) {
+                    setNotice({ kind: 'info', text: 'The undo history didn\'t match the sequence and was discarded' })
+                }
+            } catch (error) {
+                showError(error)
+            }
             setIsLoading(false)
         }
         initialize()
@@ -79,8 +179,19 @@ const useEditor = (): useEditorReturnTypes => {
 
     useEffect(() => {
         if (!activeProject?.id) return
-        setActiveProject(project => project?.updateImmutable({ sequence: sequenceModel.nucleotideString }) ?? null)
-    }, [activeProject?.id, setActiveProject, sequenceModel])
+        // The history is written by the backend on save and close, it's too large for local storage
+        const saveProject = async () => {
+            if (activeProject) {
+                await Bridge.setProjectInfo({
+                    name: activeProject.name ?? null,
+                    created_at: activeProject.createdAt.getTime(),
+                    updated_at: activeProject.updatedAt?.getTime() ?? null,
+                })
+            }
+            setActiveProject(project => project?.updateImmutable({ sequence: sequenceModel.nucleotideString, history: undefined }) ?? null)
+        }
+        saveProject().catch(showError)
+    }, [activeProject?.id, Bridge, setActiveProject, sequenceModel])
 
     useEffect(() => {
         const updateBackendSelection = async () => {
@@ -91,11 +202,27 @@ const useEditor = (): useEditorReturnTypes => {
             } else {
                 await Bridge.setSelection(selection.start, selection.end)
             }
-            await updateSequence()
         }
-        updateBackendSelection()
+        updateBackendSelection().catch(showError)
     }, [selection])
 
+    // Commands don't return the new state, it arrives with the change event
+    useEffect(() => {
+        const document = activeProject?.id
+        if (!document) return
+        const unlisten = listen<EditorChange>('editor-change', ({ payload }) => {
+            if (payload.document !== document) return
+            setCursorModel(new SequenceDataCursorModel(payload.cursor))
+            setSelectionModel(new SequenceDataSelectionModel(payload.selection))
+            if (payload.changes.length > 0) {
+                const start = Math.min(...payload.changes.map(change => change.start))
+                updateSequence(start).catch(showError)
+            }
+        })
+        return () => { unlisten.then(fn => fn()) }
+        // eslint-disable-next-line react-hooks/exhaustive-deps
+    }, [activeProject?.id])
+
     /**
      * @param {KeyboardEvent} e
      */
@@ -129,20 +256,19 @@ const useEditor = (): useEditorReturnTypes => {
                     let should_return = true
                     switch (upperKey) {
                         case 'C':
-                            await navigator.clipboard.writeText(await Bridge.getSelectedSequence())
+                            await Bridge.copy()
                             break
-                        case 'V':
-                            await (async () => {
-                                const text = await navigator.clipboard.readText()
-                                await Bridge.insertAll(text)
-                            })()
+                        case 'V': {
+                            const { summary } = await Bridge.paste(pasteOptions)
+                            const description = summary && describePasteSummary(summary)
+                            if (description) setNotice({ kind: 'info', text: description })
                             break
+                        }
                         case 'A':
                             await Bridge.selectAll()
                             break
                         case 'X':
-                            await navigator.clipboard.writeText(await Bridge.getSelectedSequence())
-                            await Bridge.delete()
+                            await Bridge.cut()
                             break
                         case 'Z':
                             await Bridge.undo()
@@ -150,6 +276,10 @@ const useEditor = (): useEditorReturnTypes => {
                         case 'Y':
                             await Bridge.redo()
                             break
+                        case 'S':
+                            await Bridge.storeProject()
+                            setNotice({ kind: 'info', text: 'Saved' })
+                            return false
                         default:
                             should_return = false
                             break
@@ -163,7 +293,7 @@ const useEditor = (): useEditorReturnTypes => {
         }
 
         return true
-    }, [])
+    }, [Bridge, pasteOptions])
 
     const handleMouseEvent = useCallback(async (e: React.MouseEvent<HTMLElement, MouseEvent>) => {
         e.preventDefault()
@@ -191,27 +321,34 @@ const useEditor = (): useEditorReturnTypes => {
             default:
                 return false
         }
-    }, [isSelecting, startSelection, updateSelection, endSelection, selection])
-
-    const updateSequence = async (force = false) => {
-        // TODO: Fix type annotations!
-        type BridgeCalculateSequenceDataType = {
-            selection: Record<string, unknown>,
-            cursor: Record<string, unknown>,
-            sequence?: Record<string, unknown>
-        }
-        const data = await Bridge.calculateSequenceData(force) as BridgeCalculateSequenceDataType
-        if (data.sequence) {
-            setSequenceModel(new SequenceDataModel(data))
-        }
-        setCursorModel(new SequenceDataCursorModel(data?.cursor))
-        setSelectionModel(new SequenceDataSelectionModel(data?.selection))
+    }, [Bridge, isSelecting, startSelection, updateSelection, endSelection, selection])
+
+    const pendingUpdate = useRef(Promise.resolve())
+
+    /**
+     * Fetches the items from `start` to the end of the sequence and patches them into the model.
+     * Updates run one after another, so that a slow update can't overwrite a newer one.
+     */
+    const updateSequence = (start: number) => {
+        const update = pendingUpdate.current.then(async () => {
+            const window = await Bridge.calculateSequenceWindow(start, Number.MAX_SAFE_INTEGER)
+            setSequenceModel(model => start === 0
+                ? new SequenceDataModel({ sequence: window.items, bp_count: window.bp_count })
+                : model.withWindow(window))
+            setCursorModel(new SequenceDataCursorModel(window.cursor))
+            setSelectionModel(new SequenceDataSelectionModel(window.selection))
+        })
+        pendingUpdate.current = update.catch(() => undefined)
+        return update
     }
 
     type RelayAsyncFn<T, R> = (...data: T[]) => Promise<R>
     const wrapUpdatingAsync = <T>(fn: RelayAsyncFn<T, boolean>) => async (...data: T[]) => {
-        if (await fn(...data)) {
-            await updateSequence()
+        try {
+            await fn(...data)
+        } catch (error) {
+            // Commands reject with an `EditorError` if they couldn't be carried out
+            showError(error)
         }
     }
 
@@ -220,6 +357,8 @@ const useEditor = (): useEditorReturnTypes => {
         cursor: cursorModel,
         sequence: sequenceModel,
         selection: selectionModel,
+        notice,
+        dismissNotice,
         handlers: {
             handleKeyDown: wrapUpdatingAsync(handleKeyDown),
             handleMouseEvent: wrapUpdatingAsync(handleMouseEvent),
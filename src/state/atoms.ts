@@ -13,6 +13,29 @@ export const editorHintState = atom({
     effects: [persistLocalEffect('editorHints')],
 })
 
+export type InvalidPolicy = 'Reject' | 'Skip' | 'ConvertToN'
+
+/** How text pasted from other apps is cleaned up, see `PasteOptions` in the backend. */
+export type PasteOptions = {
+    stripNumbers: boolean,
+    stripFastaHeaders: boolean,
+    convertRna: boolean,
+    genbank: boolean,
+    invalid: InvalidPolicy,
+}
+
+export const pasteOptionsState = atom<PasteOptions>({
+    key: 'pasteOptionsState',
+    default: {
+        stripNumbers: false,
+        stripFastaHeaders: false,
+        convertRna: false,
+        genbank: false,
+        invalid: 'Reject',
+    },
+    effects: [persistLocalEffect('pasteOptions')],
+})
+
 export const editorRendererState = atom({
     key: 'editorRendererState',
     default: 'next',
@@ -0,0 +1,54 @@
+/**
+ * An `EditorError` as the backend rejects commands with, e.g.
+ * `{ kind: 'InvalidCharacter', character: 'X', position: 4 }`.
+ */
+export type EditorError = {
+    kind: string,
+    [field: string]: unknown,
+}
+
+const isEditorError = (error: unknown): error is EditorError => (
+    typeof error === 'object' && error !== null && 'kind' in error
+)
+
+/**
+ * Describes a rejected command for the user.
+ */
+export const describeEditorError = (error: unknown): string => {
+    if (!isEditorError(error)) return String(error)
+
+    switch (error.kind) {
+        case 'InvalidCharacter':
+            return `'${error.character}' at position ${error.position} is not a nucleotide`
+        case 'InvalidLetter':
+            return `'${error.character}' is not a nucleotide`
+        case 'IndexOutOfRange':
+            return `Position ${error.index} is outside of the sequence (${error.len} bp)`
+        case 'EmptySelection':
+            return 'Nothing is selected'
+        case 'NothingToDelete':
+            return 'Nothing to delete'
+        case 'NothingToUndo':
+            return 'Nothing to undo'
+        case 'NothingToRedo':
+            return 'Nothing to redo'
+        case 'InvalidQuery':
+            return `Invalid search query '${error.query}'`
+        case 'UnknownCheckpoint':
+            return 'The checkpoint doesn\'t exist anymore'
+        case 'UnknownHistoryNode':
+            return 'The history entry doesn\'t exist anymore'
+        case 'UnknownDocument':
+            return 'The project isn\'t open'
+        case 'Clipboard':
+            return `Couldn't access the clipboard: ${error.message}`
+        case 'Io':
+            return `Couldn't read or write a file: ${error.message}`
+        case 'InvalidProjectFile':
+            return `Invalid project file: ${error.message}`
+        case 'UnsupportedVersion':
+            return `The project was saved by a newer version (format ${error.version})`
+        default:
+            return error.kind
+    }
+}